@@ -2,13 +2,14 @@ use std::{net::SocketAddr, sync::Arc};
 
 use anyhow::Result;
 use axum::{
-    extract::Path,
+    extract::{Path, Query},
     http::StatusCode,
     response::{IntoResponse, Response},
     routing::get,
     Extension, Json, Router,
 };
 use colored::Colorize;
+use serde::Deserialize;
 use tokio::{
     process::Command,
     sync::{Mutex, RwLock},
@@ -22,10 +23,9 @@ use tower_http::{
 use tracing::{event, Level};
 
 use crate::{
-    db::SqliteStorage,
-    entities::graph::FilterOptions,
+    entities::{graph::FilterOptions, storage::StorageError},
     parser::parse,
-    runner::Runner,
+    runner::{cancel::Canceller, Runner, RunnerOptions},
     services::node_info::{get_node_info, get_nodes_info},
     traits::{GraphWorkflow, RunnerWorkflow, Storage},
     workflow::Workflow,
@@ -37,14 +37,25 @@ pub struct RunnerSharedState {
     #[allow(dead_code)]
     fp: Mutex<String>,
     runner: Arc<RwLock<Runner>>,
+    /// Kept outside of the runner lock, that is held while the tests are running.
+    canceller: Canceller,
+    /// The runner storage, also kept outside of its lock.
+    storage: Arc<dyn Storage>,
 }
 
-pub async fn run_server(fp: &str, show_ui: bool) -> Result<()> {
+pub async fn run_server(fp: &str, show_ui: bool, storage: Arc<dyn Storage>) -> Result<()> {
     let manifest = parse(fp)?;
-    let runner = Arc::new(RwLock::new(Runner::new(Workflow::new(manifest)?)?));
+    let options = RunnerOptions {
+        manifest: Some(fp.to_string()),
+        ..Default::default()
+    };
+    let runner = Runner::with_options(Workflow::new(manifest)?, options, storage.clone())?;
+    let canceller = runner.canceller();
     let shared_state = Arc::new(RunnerSharedState {
         fp: Mutex::new(fp.to_string()),
-        runner,
+        runner: Arc::new(RwLock::new(runner)),
+        canceller,
+        storage,
     });
     let mut app = Router::new()
         .route("/api/runner/batch", get(batch_execute))
@@ -52,16 +63,20 @@ pub async fn run_server(fp: &str, show_ui: bool) -> Result<()> {
         .route("/api/runner/running", get(running))
         .route("/api/runner/available", get(available))
         .route("/api/runner/reset", get(reset))
+        .route("/api/runner/cancel", get(cancel))
         .route("/api/nodes", get(get_nodes))
         .route("/api/nodes/:node_id", get(get_node))
+        .route("/api/nodes/:node_id/run", get(run_target))
+        .route("/api/runs", get(get_runs))
         .layer(
             TraceLayer::new_for_http()
                 .make_span_with(DefaultMakeSpan::new().level(Level::INFO))
                 .on_response(DefaultOnResponse::new().level(Level::INFO)),
         )
-        .layer(AddExtensionLayer::new(shared_state))
+        .layer(AddExtensionLayer::new(shared_state.clone()))
         // From here, we define routes that we dont want to be traced (due to unnecessary spam)
         .route("/api/dot", get(dot))
+        .layer(AddExtensionLayer::new(shared_state))
         .layer(CorsLayer::permissive());
     if show_ui {
         app = app.nest_service("/", ServeDir::new("ui/dist"));
@@ -79,12 +94,36 @@ pub async fn run_server(fp: &str, show_ui: bool) -> Result<()> {
     Ok(())
 }
 
+#[derive(Deserialize)]
+struct RunParams {
+    /// The run id, the latest run by default
+    run: Option<i64>,
+}
+
+/// The id of the run asked for, or the latest one.
+///
+/// Responds 404 if there is no run yet and 500 if the storage fails.
+fn run_or_latest(storage: &dyn Storage, run: Option<i64>) -> Result<i64, StatusCode> {
+    match run {
+        Some(run) => Ok(run),
+        None => storage
+            .get_last_run()
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+            .map(|run| run.id)
+            .ok_or(StatusCode::NOT_FOUND),
+    }
+}
+
 /// Returns the dot representation of the current graph state.
-async fn dot() -> Result<String, StatusCode> {
-    let storage = SqliteStorage::new();
-    let last_dot = storage
-        .get_dots()
-        .map_err(|_| StatusCode::BAD_REQUEST)?
+async fn dot(
+    Extension(state): Extension<SharedState>,
+    Query(params): Query<RunParams>,
+) -> Result<String, StatusCode> {
+    let run = run_or_latest(&*state.storage, params.run)?;
+    let last_dot = state
+        .storage
+        .get_dots(run)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
         .last()
         .cloned()
         .unwrap_or_default()
@@ -94,7 +133,8 @@ async fn dot() -> Result<String, StatusCode> {
 
 /// Resets the Runner to its initial state.
 ///
-/// This means that the workflow inside the Runner and the storage will be reseted to its initial state too.
+/// This means that the workflow inside the Runner will be reseted to its initial state too,
+/// a new run is started in the storage, keeping the previous ones.
 async fn reset(Extension(state): Extension<SharedState>) -> Result<String, StatusCode> {
     state
         .runner
@@ -105,6 +145,12 @@ async fn reset(Extension(state): Extension<SharedState>) -> Result<String, Statu
         .map_err(|_| StatusCode::BAD_REQUEST)?;
     Ok("".to_string())
 }
+/// Cancel the current run, killing the running tests.
+async fn cancel(Extension(state): Extension<SharedState>) -> Result<String, StatusCode> {
+    state.canceller.cancel();
+    Ok("OK".to_string())
+}
+
 /// Run all tests until the graph exhaustion.
 async fn run_all(Extension(state): Extension<SharedState>) -> Result<String, StatusCode> {
     state
@@ -118,6 +164,33 @@ async fn run_all(Extension(state): Extension<SharedState>) -> Result<String, Sta
     Ok("OK".to_string())
 }
 
+#[derive(Deserialize)]
+struct RunTargetParams {
+    /// Reuses the ancestors already completed in the current session
+    #[serde(default)]
+    reuse: bool,
+}
+
+/// Runs the node (by test id or index) and its ancestors, returning the node with its final status.
+///
+/// Responds 404 if the node doesn't exist and 500 if the storage fails.
+async fn run_target(
+    Path(node_id): Path<String>,
+    Query(params): Query<RunTargetParams>,
+    Extension(state): Extension<SharedState>,
+) -> Response {
+    let mut runner = state.runner.write().await;
+    if runner.workflow.read().await.find_node(&node_id).is_none() {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+    match runner.run_target(&node_id, params.reuse).await {
+        Ok(node) => Json(node).into_response(),
+        // the run couldn't be kept, it isn't a problem of the request
+        Err(err) if err.is::<StorageError>() => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+        Err(_) => StatusCode::BAD_REQUEST.into_response(),
+    }
+}
+
 /// Check if the workflow is exhausted or not.
 async fn available(Extension(state): Extension<SharedState>) -> Result<String, StatusCode> {
     let availables = state
@@ -167,16 +240,43 @@ async fn batch_execute(Extension(state): Extension<SharedState>) -> Result<Strin
     return Ok(x.read().await.workflow.read().await.as_dot());
 }
 
-async fn get_node(Path(node_id): Path<u32>) -> Response {
-    match get_node_info(node_id as i32) {
+async fn get_node(
+    Extension(state): Extension<SharedState>,
+    Path(node_id): Path<u32>,
+    Query(params): Query<RunParams>,
+) -> Response {
+    let run = match run_or_latest(&*state.storage, params.run) {
+        Ok(run) => run,
+        Err(status) => return status.into_response(),
+    };
+    match get_node_info(&*state.storage, run, node_id as i32) {
         Ok(node) => Json(node).into_response(),
+        // the run couldn't be kept, it isn't a problem of the request
+        Err(err) if err.is::<StorageError>() => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
         Err(_) => StatusCode::BAD_REQUEST.into_response(),
     }
 }
 
-async fn get_nodes() -> Response {
-    match get_nodes_info() {
+async fn get_nodes(
+    Extension(state): Extension<SharedState>,
+    Query(params): Query<RunParams>,
+) -> Response {
+    let run = match run_or_latest(&*state.storage, params.run) {
+        Ok(run) => run,
+        Err(status) => return status.into_response(),
+    };
+    match get_nodes_info(&*state.storage, run) {
         Ok(node) => Json(node).into_response(),
+        // the run couldn't be kept, it isn't a problem of the request
+        Err(err) if err.is::<StorageError>() => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
         Err(_) => StatusCode::BAD_REQUEST.into_response(),
     }
 }
+
+/// Lists all the runs kept in the storage, the oldest first.
+async fn get_runs(Extension(state): Extension<SharedState>) -> Response {
+    match state.storage.get_runs() {
+        Ok(runs) => Json(runs).into_response(),
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
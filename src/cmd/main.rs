@@ -1,11 +1,16 @@
+use std::{path::PathBuf, time::Duration};
+
 use anyhow::Result;
-use clap::{Parser, Subcommand};
+use clap::{Args, Parser, Subcommand};
 use thorust::{
     api::run_server,
+    db::{default_path, open_storage, SqliteStorage},
+    entities::{enums::StorageKind, graph::FilterOptions},
+    lint::lint,
     parser::parse,
-    runner::Runner,
+    runner::{Runner, RunnerOptions},
     traits::{GraphWorkflow, RunnerWorkflow},
-    workflow::Workflow,
+    workflow::{Selection, Workflow},
 };
 use tracing::Level;
 
@@ -17,14 +22,82 @@ struct ThorustCmd {
     /// Manifest file to read
     #[command(subcommand)]
     command: Commands,
+    /// Database file (or directory, for jsonl) that keeps the runs history, `THORUST_DB` or `./db` by default
+    #[clap(long, global = true)]
+    db: Option<PathBuf>,
+    /// Storage backend of the runs history: sqlite, jsonl or memory
+    #[clap(long, global = true, default_value = "sqlite")]
+    storage: StorageKind,
+}
+
+// Selects the tests to run, their dependencies are always selected as well.
+// Not a doc comment, clap would take it as the about of the commands that flatten it.
+#[derive(Args)]
+struct SelectionArgs {
+    /// Only the tests with any of these tags, e.g: `--tags smoke,slow`
+    #[clap(long, value_delimiter = ',')]
+    tags: Vec<String>,
+    /// Only the tests of this service
+    #[clap(long)]
+    service: Option<String>,
+    /// Only the tests with ids matching any of these globs, e.g: `--only 'foo.*'`
+    #[clap(long, value_delimiter = ',')]
+    only: Vec<String>,
+    /// Leaves out the tests with ids matching any of these globs, e.g: `--exclude bar.test2`
+    #[clap(long, value_delimiter = ',')]
+    exclude: Vec<String>,
+}
+
+impl SelectionArgs {
+    /// Applies the selection over the workflow, if any
+    fn select(&self, workflow: &mut Workflow) -> Result<()> {
+        if self.tags.is_empty()
+            && self.service.is_none()
+            && self.only.is_empty()
+            && self.exclude.is_empty()
+        {
+            return Ok(());
+        }
+        let exclude = FilterOptions {
+            globs: self.exclude.clone(),
+            ..Default::default()
+        };
+        workflow.select(Selection {
+            include: FilterOptions {
+                tags: self.tags.clone(),
+                service: self.service.clone(),
+                globs: self.only.clone(),
+                ..Default::default()
+            },
+            exclude: (!self.exclude.is_empty()).then_some(exclude),
+        })
+    }
 }
 
 #[derive(Subcommand)]
 enum Commands {
+    /// Runs the tests and prints the final workflow as JSON
     Run {
         /// Manifest file to read
         #[clap(short, long)]
         file: String,
+        /// Maximum duration of the whole run, e.g: `10m` or `1h 30m`
+        #[clap(long, value_parser = humantime::parse_duration)]
+        timeout: Option<Duration>,
+        /// Maximum number of tests running at the same time
+        #[clap(short, long)]
+        jobs: Option<usize>,
+        /// Stops starting new tests after the first failure
+        #[clap(long)]
+        fail_fast: bool,
+        /// With --fail-fast, cancels the tests still running instead of letting them finish
+        #[clap(long, requires = "fail_fast")]
+        cancel_running: bool,
+        #[command(flatten)]
+        selection: SelectionArgs,
+        /// Only runs this test (by id) and its ancestors, e.g: `--target bar.test1`
+        #[clap(long)]
+        target: Option<String>,
     },
     Api {
         /// Manifest file to read
@@ -41,9 +114,36 @@ enum Commands {
         /// Manifest file to read
         #[clap(short, long)]
         file: String,
+        #[command(flatten)]
+        selection: SelectionArgs,
+    },
+    /// Checks the manifests without running them, exits with an error code if they are invalid
+    #[command(alias = "lint")]
+    Validate {
+        /// Manifest file to read
+        #[clap(short, long)]
+        file: String,
+        /// Prints the report as JSON
+        #[clap(long)]
+        json: bool,
+    },
+    /// Prints the history of the runs kept in the database as JSON
+    Runs,
+    /// Manages the schema of the sqlite database
+    Db {
+        #[command(subcommand)]
+        command: DbCommands,
     },
 }
 
+#[derive(Subcommand)]
+enum DbCommands {
+    /// Applies the pending migrations, they are also applied on every other command
+    Migrate,
+    /// Prints the schema version and the migrations, applied or pending
+    Info,
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
     let format = tracing_subscriber::fmt::format()
@@ -53,29 +153,91 @@ async fn main() -> Result<()> {
         .event_format(format)
         .with_max_level(Level::INFO)
         .init();
-    // resets db to ensure a clean state on every CLI execution
-    let _ = std::fs::remove_file("./db");
     let args = ThorustCmd::parse();
+    let storage = || open_storage(args.storage, args.db.clone());
 
     match &args.command {
-        Commands::Run { file } => {
-            let manifest = parse(&file).unwrap();
-            let workflow = Workflow::new(manifest)?;
-            let mut runner = Runner::new(workflow)?;
-            runner.run_until_complete().await?;
+        Commands::Run {
+            file,
+            timeout,
+            jobs,
+            fail_fast,
+            cancel_running,
+            selection,
+            target,
+        } => {
+            let manifest = parse(file).unwrap();
+            let mut workflow = Workflow::new(manifest)?;
+            selection.select(&mut workflow)?;
+            let options = RunnerOptions {
+                timeout: *timeout,
+                jobs: *jobs,
+                fail_fast: *fail_fast,
+                cancel_running: *cancel_running,
+                manifest: Some(file.clone()),
+            };
+            let mut runner = Runner::with_options(workflow, options, storage()?)?;
+            // Ctrl-C cancels the run, the report is still printed. A second one exits right away.
+            let canceller = runner.canceller();
+            tokio::spawn(async move {
+                if tokio::signal::ctrl_c().await.is_ok() {
+                    canceller.cancel();
+                    tracing::event!(
+                        Level::WARN,
+                        "Cancelling the run, press Ctrl-C again to exit"
+                    );
+                    if tokio::signal::ctrl_c().await.is_ok() {
+                        std::process::exit(130);
+                    }
+                }
+            });
+            match target {
+                Some(target) => {
+                    runner.run_target(target, false).await?;
+                }
+                None => runner.run_until_complete().await?,
+            }
             println!("{}", runner.workflow.read().await.as_json());
         }
         Commands::Api { file } => {
-            run_server(&file, false).await?;
+            run_server(file, false, storage()?).await?;
         }
         Commands::Ui { file } => {
-            run_server(&file, true).await?;
+            run_server(file, true, storage()?).await?;
         }
-        Commands::Dot { file } => {
-            let manifest = parse(&file).unwrap();
-            let workflow = Workflow::new(manifest)?;
+        Commands::Dot { file, selection } => {
+            let manifest = parse(file).unwrap();
+            let mut workflow = Workflow::new(manifest)?;
+            selection.select(&mut workflow)?;
             println!("{}", workflow.as_dot());
         }
+        Commands::Validate { file, json } => {
+            let report = lint(file);
+            match json {
+                true => println!("{}", serde_json::to_string_pretty(&report)?),
+                false => println!("{}", report),
+            }
+            if !report.is_ok() {
+                std::process::exit(1);
+            }
+        }
+        Commands::Runs => {
+            let runs = storage()?.get_runs()?;
+            println!("{}", serde_json::to_string_pretty(&runs)?);
+        }
+        Commands::Db { command } => {
+            if args.storage != StorageKind::Sqlite {
+                anyhow::bail!("The db commands only apply to the sqlite storage");
+            }
+            let storage = SqliteStorage::connect(args.db.clone().unwrap_or_else(default_path))?;
+            match command {
+                DbCommands::Migrate => match storage.migrate()?.as_slice() {
+                    [] => println!("The database is up to date"),
+                    applied => println!("Applied the migrations: {:?}", applied),
+                },
+                DbCommands::Info => println!("{}", storage.schema_info()?),
+            }
+        }
     }
     Ok(())
 }
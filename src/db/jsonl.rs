@@ -0,0 +1,272 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    sync::{Mutex, MutexGuard},
+};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{
+    entities::{
+        graph::TestNode,
+        storage::{DbGraph, DbNode, DbRun, NodeHistory, ProcessedHistory, StorageResult},
+    },
+    traits::Storage,
+};
+
+use super::memory::{now, process_history};
+
+const RUNS: &str = "runs.jsonl";
+const NODES: &str = "nodes.jsonl";
+const NODE_HISTORY: &str = "node_history.jsonl";
+const GRAPH: &str = "graph.jsonl";
+
+/// Storage that appends every record as a JSON line to a file per table, inside a directory.
+///
+/// The files are plain artifacts, e.g: to be kept by the CI.
+/// Nothing is ever rewritten, a finished run is appended again with its final counts,
+/// so the last line of a run is the one that counts.
+#[derive(Debug)]
+pub struct JsonLinesStorage {
+    dir: PathBuf,
+    /// The last ids given, the lock also serializes the writes
+    ids: Mutex<LastIds>,
+}
+
+#[derive(Debug, Default)]
+struct LastIds {
+    run: i64,
+    history: i64,
+    graph: i64,
+}
+
+/// A record of a run scoped table.
+#[derive(Serialize, Deserialize)]
+struct Row<T> {
+    run: i64,
+    #[serde(flatten)]
+    record: T,
+}
+
+impl JsonLinesStorage {
+    /// Opens the storage in the given directory, it is created if it doesn't exist.
+    pub fn open(dir: impl AsRef<Path>) -> StorageResult<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        std::fs::create_dir_all(&dir)?;
+        let storage = Self {
+            dir,
+            ids: Mutex::default(),
+        };
+        let ids = LastIds {
+            run: storage.get_runs()?.last().map_or(0, |run| run.id),
+            history: storage
+                .read::<Row<NodeHistory>>(NODE_HISTORY)?
+                .last()
+                .map_or(0, |row| row.record.id as i64),
+            graph: storage
+                .read::<Row<DbGraph>>(GRAPH)?
+                .last()
+                .map_or(0, |row| row.record.id as i64),
+        };
+        *storage.ids() = ids;
+        Ok(storage)
+    }
+
+    fn ids(&self) -> MutexGuard<'_, LastIds> {
+        self.ids.lock().unwrap()
+    }
+
+    fn append(&self, file: &str, record: &impl Serialize) -> StorageResult<()> {
+        let mut line = serde_json::to_string(record)?;
+        line.push('\n');
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.dir.join(file))?
+            .write_all(line.as_bytes())?;
+        Ok(())
+    }
+
+    fn read<T: DeserializeOwned>(&self, file: &str) -> StorageResult<Vec<T>> {
+        let file = match File::open(self.dir.join(file)) {
+            Ok(file) => file,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(err) => return Err(err.into()),
+        };
+        let mut records = vec![];
+        for line in BufReader::new(file).lines() {
+            let line = line?;
+            if !line.trim().is_empty() {
+                records.push(serde_json::from_str(&line)?);
+            }
+        }
+        Ok(records)
+    }
+
+    /// The records of the table that belong to the run.
+    fn read_run<T: DeserializeOwned>(&self, file: &str, run: i64) -> StorageResult<Vec<T>> {
+        Ok(self
+            .read::<Row<T>>(file)?
+            .into_iter()
+            .filter(|row| row.run == run)
+            .map(|row| row.record)
+            .collect())
+    }
+}
+
+impl Storage for JsonLinesStorage {
+    fn insert_run(&self, manifest: &str) -> StorageResult<i64> {
+        let mut ids = self.ids();
+        let run = DbRun {
+            id: ids.run + 1,
+            manifest: manifest.to_string(),
+            started_at: now(),
+            ..Default::default()
+        };
+        self.append(RUNS, &run)?;
+        ids.run = run.id;
+        Ok(run.id)
+    }
+
+    fn finish_run(&self, run: &DbRun) -> StorageResult<()> {
+        let _ids = self.ids();
+        let Some(current) = self
+            .read::<DbRun>(RUNS)?
+            .into_iter()
+            .rfind(|r| r.id == run.id)
+        else {
+            return Ok(());
+        };
+        self.append(
+            RUNS,
+            &DbRun {
+                manifest: current.manifest,
+                started_at: current.started_at,
+                finished_at: Some(now()),
+                ..run.clone()
+            },
+        )
+    }
+
+    fn get_runs(&self) -> StorageResult<Vec<DbRun>> {
+        let mut runs: Vec<DbRun> = vec![];
+        for run in self.read::<DbRun>(RUNS)? {
+            match runs.iter_mut().find(|r| r.id == run.id) {
+                Some(current) => *current = run,
+                None => runs.push(run),
+            }
+        }
+        runs.sort_by_key(|run| run.id);
+        Ok(runs)
+    }
+
+    fn get_last_run(&self) -> StorageResult<Option<DbRun>> {
+        Ok(self.get_runs()?.pop())
+    }
+
+    fn insert_test_node(&self, run: i64, node: &TestNode) -> StorageResult<()> {
+        let node_id = self.insert_node(run, node.clone().into())?;
+        self.insert_node_history(
+            run,
+            &node.last_status().to_string(),
+            node_id,
+            &node.executable.output.clone().unwrap_or_default(),
+        )?;
+        Ok(())
+    }
+
+    fn insert_node(&self, run: i64, node: DbNode) -> StorageResult<i64> {
+        let _ids = self.ids();
+        let id = node.id as i64;
+        self.append(NODES, &Row { run, record: node })?;
+        Ok(id)
+    }
+
+    fn insert_node_history(
+        &self,
+        run: i64,
+        status: &str,
+        node_id: i64,
+        data: &str,
+    ) -> StorageResult<i64> {
+        let mut ids = self.ids();
+        let record = NodeHistory {
+            id: ids.history as i32 + 1,
+            status: status.to_string(),
+            node: node_id as i32,
+            data: data.to_string(),
+            created_at: now(),
+        };
+        self.append(NODE_HISTORY, &Row { run, record })?;
+        ids.history += 1;
+        Ok(ids.history)
+    }
+
+    fn insert_dot(&self, run: i64, dot: &str) -> StorageResult<i64> {
+        let mut ids = self.ids();
+        let record = DbGraph {
+            id: ids.graph as i32 + 1,
+            dot: dot.to_string(),
+            created_at: now(),
+        };
+        self.append(GRAPH, &Row { run, record })?;
+        ids.graph += 1;
+        Ok(ids.graph)
+    }
+
+    fn get_nodes(&self, run: i64, ids: &[i32]) -> StorageResult<Vec<DbNode>> {
+        Ok(self
+            .get_all_nodes(run)?
+            .into_iter()
+            .filter(|node| ids.contains(&node.id))
+            .collect())
+    }
+
+    fn get_all_nodes(&self, run: i64) -> StorageResult<Vec<DbNode>> {
+        let mut nodes = self.read_run::<DbNode>(NODES, run)?;
+        nodes.sort_by_key(|node| node.id);
+        Ok(nodes)
+    }
+
+    fn get_node_history(&self, run: i64, node_id: i32) -> StorageResult<Vec<NodeHistory>> {
+        Ok(self
+            .read_run::<NodeHistory>(NODE_HISTORY, run)?
+            .into_iter()
+            .filter(|h| h.node == node_id)
+            .collect())
+    }
+
+    fn get_processed_node_history(
+        &self,
+        run: i64,
+        node_id: i32,
+    ) -> StorageResult<Vec<ProcessedHistory>> {
+        Ok(process_history(&self.get_node_history(run, node_id)?))
+    }
+
+    fn get_all_processed_node_history(&self, run: i64) -> StorageResult<Vec<ProcessedHistory>> {
+        let history = self.read_run::<NodeHistory>(NODE_HISTORY, run)?;
+        let mut processed = vec![];
+        for node in self.get_all_nodes(run)? {
+            let node_history = history
+                .iter()
+                .filter(|h| h.node == node.id)
+                .cloned()
+                .collect::<Vec<NodeHistory>>();
+            processed.extend(process_history(&node_history));
+        }
+        Ok(processed)
+    }
+
+    fn get_dots(&self, run: i64) -> StorageResult<Vec<DbGraph>> {
+        self.read_run::<DbGraph>(GRAPH, run)
+    }
+
+    fn insert_test_nodes(&self, run: i64, nodes: Vec<&TestNode>) -> StorageResult<()> {
+        for node in nodes {
+            self.insert_test_node(run, node)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,222 @@
+use std::sync::{Mutex, MutexGuard};
+
+use chrono::{NaiveDateTime, Utc};
+
+use crate::{
+    entities::{
+        graph::TestNode,
+        storage::{DbGraph, DbNode, DbRun, NodeHistory, ProcessedHistory, StorageResult},
+    },
+    traits::Storage,
+};
+
+/// The timestamps format, the same one SQLite uses.
+pub(crate) const TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S%.3f";
+
+/// Storage that keeps everything in memory, nothing is written to the filesystem.
+///
+/// Useful when thorust is embedded or in tests, the history is lost when it's dropped.
+#[derive(Debug, Default)]
+pub struct MemoryStorage {
+    state: Mutex<MemoryState>,
+}
+
+/// The rows of each table, along with their run id.
+#[derive(Debug, Default)]
+struct MemoryState {
+    runs: Vec<DbRun>,
+    nodes: Vec<(i64, DbNode)>,
+    history: Vec<(i64, NodeHistory)>,
+    graphs: Vec<(i64, DbGraph)>,
+}
+
+impl MemoryStorage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn state(&self) -> MutexGuard<'_, MemoryState> {
+        self.state.lock().unwrap()
+    }
+}
+
+pub(crate) fn now() -> String {
+    Utc::now().format(TIMESTAMP_FORMAT).to_string()
+}
+
+/// Milliseconds elapsed between two timestamps.
+fn duration_millis(from: &str, to: &str) -> f64 {
+    let parse = |t: &str| NaiveDateTime::parse_from_str(t, TIMESTAMP_FORMAT);
+    match (parse(from), parse(to)) {
+        (Ok(from), Ok(to)) => (to - from).num_milliseconds() as f64,
+        _ => 0.0,
+    }
+}
+
+/// Pairs every status of the node history with the following one.
+pub(crate) fn process_history(history: &[NodeHistory]) -> Vec<ProcessedHistory> {
+    history
+        .windows(2)
+        .map(|pair| ProcessedHistory {
+            node: pair[0].node as u32,
+            from_status: pair[0].status.clone(),
+            to_status: pair[1].status.clone(),
+            from_created_at: pair[0].created_at.clone(),
+            to_created_at: pair[1].created_at.clone(),
+            duration_millis: duration_millis(&pair[0].created_at, &pair[1].created_at),
+        })
+        .collect()
+}
+
+impl Storage for MemoryStorage {
+    fn insert_run(&self, manifest: &str) -> StorageResult<i64> {
+        let mut state = self.state();
+        let id = state.runs.len() as i64 + 1;
+        state.runs.push(DbRun {
+            id,
+            manifest: manifest.to_string(),
+            started_at: now(),
+            ..Default::default()
+        });
+        Ok(id)
+    }
+
+    fn finish_run(&self, run: &DbRun) -> StorageResult<()> {
+        let mut state = self.state();
+        if let Some(current) = state.runs.iter_mut().find(|r| r.id == run.id) {
+            *current = DbRun {
+                manifest: current.manifest.clone(),
+                started_at: current.started_at.clone(),
+                finished_at: Some(now()),
+                ..run.clone()
+            };
+        }
+        Ok(())
+    }
+
+    fn get_runs(&self) -> StorageResult<Vec<DbRun>> {
+        Ok(self.state().runs.clone())
+    }
+
+    fn get_last_run(&self) -> StorageResult<Option<DbRun>> {
+        Ok(self.state().runs.last().cloned())
+    }
+
+    fn insert_test_node(&self, run: i64, node: &TestNode) -> StorageResult<()> {
+        let node_id = self.insert_node(run, node.clone().into())?;
+        self.insert_node_history(
+            run,
+            &node.last_status().to_string(),
+            node_id,
+            &node.executable.output.clone().unwrap_or_default(),
+        )?;
+        Ok(())
+    }
+
+    fn insert_node(&self, run: i64, node: DbNode) -> StorageResult<i64> {
+        let id = node.id as i64;
+        self.state().nodes.push((run, node));
+        Ok(id)
+    }
+
+    fn insert_node_history(
+        &self,
+        run: i64,
+        status: &str,
+        node_id: i64,
+        data: &str,
+    ) -> StorageResult<i64> {
+        let mut state = self.state();
+        let id = state.history.len() as i32 + 1;
+        state.history.push((
+            run,
+            NodeHistory {
+                id,
+                status: status.to_string(),
+                node: node_id as i32,
+                data: data.to_string(),
+                created_at: now(),
+            },
+        ));
+        Ok(id as i64)
+    }
+
+    fn insert_dot(&self, run: i64, dot: &str) -> StorageResult<i64> {
+        let mut state = self.state();
+        let id = state.graphs.len() as i32 + 1;
+        state.graphs.push((
+            run,
+            DbGraph {
+                id,
+                dot: dot.to_string(),
+                created_at: now(),
+            },
+        ));
+        Ok(id as i64)
+    }
+
+    fn get_nodes(&self, run: i64, ids: &[i32]) -> StorageResult<Vec<DbNode>> {
+        let mut nodes = self
+            .get_all_nodes(run)?
+            .into_iter()
+            .filter(|node| ids.contains(&node.id))
+            .collect::<Vec<DbNode>>();
+        nodes.sort_by_key(|node| node.id);
+        Ok(nodes)
+    }
+
+    fn get_all_nodes(&self, run: i64) -> StorageResult<Vec<DbNode>> {
+        let mut nodes = self
+            .state()
+            .nodes
+            .iter()
+            .filter(|(r, _)| *r == run)
+            .map(|(_, node)| node.clone())
+            .collect::<Vec<DbNode>>();
+        nodes.sort_by_key(|node| node.id);
+        Ok(nodes)
+    }
+
+    fn get_node_history(&self, run: i64, node_id: i32) -> StorageResult<Vec<NodeHistory>> {
+        Ok(self
+            .state()
+            .history
+            .iter()
+            .filter(|(r, h)| *r == run && h.node == node_id)
+            .map(|(_, h)| h.clone())
+            .collect())
+    }
+
+    fn get_processed_node_history(
+        &self,
+        run: i64,
+        node_id: i32,
+    ) -> StorageResult<Vec<ProcessedHistory>> {
+        Ok(process_history(&self.get_node_history(run, node_id)?))
+    }
+
+    fn get_all_processed_node_history(&self, run: i64) -> StorageResult<Vec<ProcessedHistory>> {
+        let mut processed = vec![];
+        for node in self.get_all_nodes(run)? {
+            processed.extend(self.get_processed_node_history(run, node.id)?);
+        }
+        Ok(processed)
+    }
+
+    fn get_dots(&self, run: i64) -> StorageResult<Vec<DbGraph>> {
+        Ok(self
+            .state()
+            .graphs
+            .iter()
+            .filter(|(r, _)| *r == run)
+            .map(|(_, graph)| graph.clone())
+            .collect())
+    }
+
+    fn insert_test_nodes(&self, run: i64, nodes: Vec<&TestNode>) -> StorageResult<()> {
+        for node in nodes {
+            self.insert_test_node(run, node)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,104 @@
+use rusqlite::Connection;
+
+/// A change of the database schema, applied once and in order of version.
+#[derive(Debug, Clone, Copy)]
+pub struct Migration {
+    pub version: u32,
+    pub description: &'static str,
+    /// Runs in the same transaction before the sql, for the changes that depend on the database
+    pub prepare: Option<fn(&Connection) -> rusqlite::Result<()>>,
+    pub sql: &'static str,
+}
+
+/// All the migrations, ordered by version.
+///
+/// A released migration must never change, a new one has to be added instead.
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "create the runs, nodes, node_history and graph tables",
+        prepare: Some(rename_legacy_tables),
+        sql: CREATE_TABLES,
+    },
+    Migration {
+        version: 2,
+        description: "index the node history and the graphs by run",
+        prepare: None,
+        sql: CREATE_RUN_INDEXES,
+    },
+];
+
+pub const CREATE_MIGRATIONS_TABLE: &str = "
+CREATE TABLE IF NOT EXISTS schema_migrations (
+    version         INTEGER PRIMARY KEY,
+    description     TEXT NOT NULL,
+    applied_at      TIMESTAMP DEFAULT(STRFTIME('%Y-%m-%d %H:%M:%f', 'NOW'))
+);";
+
+/// The tables of the history before the runs, the children first so their foreign keys
+/// follow the renamed parents.
+const LEGACY_TABLES: [&str; 3] = ["node_history", "nodes", "graph"];
+
+/// Renames the tables created before the history was kept by run (no `run` column)
+/// to `<table>_legacy`, their rows are kept there but they aren't read anymore.
+fn rename_legacy_tables(conn: &Connection) -> rusqlite::Result<()> {
+    for table in LEGACY_TABLES {
+        let exists = conn
+            .prepare("SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?1")?
+            .exists([table])?;
+        let has_run = conn
+            .prepare("SELECT 1 FROM pragma_table_info(?1) WHERE name = 'run'")?
+            .exists([table])?;
+        if exists && !has_run {
+            conn.execute_batch(&format!("ALTER TABLE {0} RENAME TO {0}_legacy;", table))?;
+        }
+    }
+    Ok(())
+}
+
+// The databases created before the migrations may already have the tables:
+// the ones that keep the runs have this same schema, the older ones are renamed first.
+const CREATE_TABLES: &str = "
+CREATE TABLE IF NOT EXISTS runs (
+    id                  INTEGER PRIMARY KEY,
+    manifest            TEXT NOT NULL,
+    started_at          TIMESTAMP DEFAULT(STRFTIME('%Y-%m-%d %H:%M:%f', 'NOW')),
+    finished_at         TIMESTAMP,
+    completed           INTEGER NOT NULL DEFAULT 0,
+    failed              INTEGER NOT NULL DEFAULT 0,
+    assertion_failed    INTEGER NOT NULL DEFAULT 0,
+    skipped             INTEGER NOT NULL DEFAULT 0,
+    cancelled           INTEGER NOT NULL DEFAULT 0,
+    total               INTEGER NOT NULL DEFAULT 0
+);
+-- the nodes ids are their indexes in the graph, so they are only unique within a run
+CREATE TABLE IF NOT EXISTS nodes (
+    run             INTEGER NOT NULL,
+    id              INTEGER NOT NULL,
+    name            TEXT NOT NULL,
+    description     TEXT NOT NULL,
+    service         TEXT NOT NULL,
+    test_id         TEXT NOT NULL,
+    PRIMARY KEY(run, id),
+    FOREIGN KEY(run) REFERENCES runs(id)
+);
+CREATE TABLE IF NOT EXISTS node_history (
+    id              INTEGER PRIMARY KEY,
+    run             INTEGER NOT NULL,
+    status          TEXT NOT NULL,
+    node            INTEGER NOT NULL,
+    created_at      TIMESTAMP DEFAULT(STRFTIME('%Y-%m-%d %H:%M:%f', 'NOW')),
+    data            TEXT DEFAULT '',
+    FOREIGN KEY(run, node) REFERENCES nodes(run, id)
+);
+CREATE TABLE IF NOT EXISTS graph (
+    id              INTEGER PRIMARY KEY,
+    run             INTEGER NOT NULL,
+    dot             TEXT NOT NULL,
+    created_at      TIMESTAMP DEFAULT(STRFTIME('%Y-%m-%d %H:%M:%f', 'NOW')),
+    FOREIGN KEY(run) REFERENCES runs(id)
+);";
+
+const CREATE_RUN_INDEXES: &str = "
+CREATE INDEX node_history_run_node ON node_history (run, node);
+CREATE INDEX graph_run ON graph (run);";
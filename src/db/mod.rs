@@ -1,116 +1,287 @@
-use std::rc::Rc;
+use std::{
+    ffi::OsString,
+    path::{Path, PathBuf},
+    rc::Rc,
+    sync::{Arc, Mutex, MutexGuard},
+    time::Duration,
+};
 
-use rusqlite::{Connection, Result};
+use rusqlite::{Connection, TransactionBehavior};
 
 use crate::{
     entities::{
+        enums::StorageKind,
         graph::TestNode,
-        storage::{DbGraph, DbNode, NodeHistory, ProcessedHistory},
+        storage::{
+            DbGraph, DbNode, DbRun, NodeHistory, ProcessedHistory, SchemaInfo, SchemaMigration,
+            StorageError, StorageResult,
+        },
     },
     traits::Storage,
 };
 
-use self::queries::{
-    ALL_HISTORY_WITH_DURATION_BETWEEN_STATUS, HISTORY_WITH_DURATION_BETWEEN_STATUS,
+pub use self::{jsonl::JsonLinesStorage, memory::MemoryStorage};
+use self::{
+    migrations::{CREATE_MIGRATIONS_TABLE, MIGRATIONS},
+    queries::{ALL_HISTORY_WITH_DURATION_BETWEEN_STATUS, HISTORY_WITH_DURATION_BETWEEN_STATUS},
 };
+
+pub mod jsonl;
+pub mod memory;
+pub mod migrations;
 pub mod queries;
 
+/// The environment variable with the database path.
+pub const DB_ENV: &str = "THORUST_DB";
+
+/// The database path used by default: `THORUST_DB` if set, `./db` otherwise.
+pub fn default_path() -> PathBuf {
+    path_from(std::env::var_os(DB_ENV))
+}
+
+/// The database path given the `THORUST_DB` value, if any.
+pub fn path_from(env: Option<OsString>) -> PathBuf {
+    env.map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("./db"))
+}
+
+/// Opens one of the bundled storages, at the given path or the default one.
+///
+/// The path is a file for SQLite, a directory for JSON lines and it is ignored in memory.
+pub fn open_storage(kind: StorageKind, path: Option<PathBuf>) -> StorageResult<Arc<dyn Storage>> {
+    let path = path.unwrap_or_else(default_path);
+    Ok(match kind {
+        StorageKind::Sqlite => Arc::new(SqliteStorage::open(path)?),
+        StorageKind::Jsonl => Arc::new(JsonLinesStorage::open(path)?),
+        StorageKind::Memory => Arc::new(MemoryStorage::new()),
+    })
+}
+
+/// How long a write waits for the database to be unlocked by another process (e.g: the API and the CLI).
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// SQLite storage, all the runner tasks share the same connection.
 #[derive(Debug)]
-pub struct SqliteStorage {}
+pub struct SqliteStorage {
+    conn: Mutex<Connection>,
+}
 
 impl SqliteStorage {
-    // Create a new and empty instance of the storage
-    pub fn new() -> Self {
-        let conn = Connection::open("./db").unwrap();
-        conn.execute(
-            "CREATE TABLE IF NOT EXISTS nodes (
-                id              INTEGER PRIMARY KEY,
-                name            TEXT NOT NULL,
-                description     TEXT NOT NULL,
-                service         TEXT NOT NULL,
-                test_id         TEXT NOT NULL
-            )",
-            (),
-        )
-        .unwrap();
-        conn.execute(
-            "CREATE TABLE IF NOT EXISTS node_history (
-                id              INTEGER PRIMARY KEY,
-                status          TEXT NOT NULL,
-                node            INTEGER NOT NULL,
-                created_at      TIMESTAMP DEFAULT(STRFTIME('%Y-%m-%d %H:%M:%f', 'NOW')),
-                data            TEXT DEFAULT '',
-                FOREIGN KEY(node) REFERENCES nodes(id)
-            )",
-            (),
-        )
-        .unwrap();
-        conn.execute(
-            "CREATE TABLE IF NOT EXISTS graph (
-                id              INTEGER PRIMARY KEY,
-                dot             TEXT NOT NULL,
-                created_at      TIMESTAMP DEFAULT(STRFTIME('%Y-%m-%d %H:%M:%f', 'NOW'))
-              )",
-            (),
-        )
-        .unwrap();
-        Self {}
-    }
-    pub fn conn(&self) -> Connection {
-        Connection::open("./db").unwrap()
+    // Create a new instance of the storage, at the default path
+    pub fn new() -> StorageResult<Self> {
+        Self::open(default_path())
+    }
+
+    // Opens the storage at the given path, the database is created if it doesn't exist
+    // and the pending migrations are applied
+    pub fn open(path: impl AsRef<Path>) -> StorageResult<Self> {
+        let storage = Self::connect(path)?;
+        storage.migrate()?;
+        Ok(storage)
+    }
+
+    // Opens the storage at the given path without migrating it, e.g: to check its schema
+    pub fn connect(path: impl AsRef<Path>) -> StorageResult<Self> {
+        let conn = Connection::open(path)?;
+        // WAL lets the readers (e.g: the API) go on while the runner writes
+        conn.pragma_update_and_check(None, "journal_mode", "WAL", |_| Ok(()))?;
+        conn.busy_timeout(BUSY_TIMEOUT)?;
+        rusqlite::vtab::array::load_module(&conn)?;
+        conn.execute_batch(CREATE_MIGRATIONS_TABLE)?;
+        Ok(Self {
+            conn: Mutex::new(conn),
+        })
+    }
+    pub fn conn(&self) -> MutexGuard<'_, Connection> {
+        self.conn.lock().unwrap()
+    }
+
+    /// Applies the pending migrations in order, each one in its own transaction.
+    ///
+    /// It runs when the storage is opened, see `SqliteStorage::open`.
+    ///
+    /// Returns the versions applied, it fails if the database is newer than this version of thorust.
+    pub fn migrate(&self) -> StorageResult<Vec<u32>> {
+        let mut conn = self.conn();
+        let version = schema_version(&conn)?;
+        let latest = MIGRATIONS.last().map_or(0, |m| m.version);
+        if version > latest {
+            return Err(StorageError::Database(format!(
+                "The database schema version {} is newer than the latest one supported {}",
+                version, latest
+            )));
+        }
+        let mut applied = vec![];
+        for migration in MIGRATIONS.iter().filter(|m| m.version > version) {
+            // another process may be migrating the same database meanwhile
+            let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+            if schema_version(&tx)? >= migration.version {
+                continue;
+            }
+            if let Some(prepare) = migration.prepare {
+                prepare(&tx)?;
+            }
+            tx.execute_batch(migration.sql)?;
+            tx.execute(
+                "INSERT INTO schema_migrations (version, description) VALUES (?1, ?2)",
+                (migration.version, migration.description),
+            )?;
+            tx.commit()?;
+            applied.push(migration.version);
+        }
+        Ok(applied)
+    }
+
+    /// The schema version and all the known migrations, applied or pending.
+    pub fn schema_info(&self) -> StorageResult<SchemaInfo> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare(
+            "SELECT version, description, applied_at FROM schema_migrations ORDER BY version ASC",
+        )?;
+        let mut migrations = stmt
+            .query_map([], |row| {
+                Ok(SchemaMigration {
+                    version: row.get(0)?,
+                    description: row.get(1)?,
+                    applied_at: row.get(2)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<SchemaMigration>>>()?;
+        let version = migrations.last().map_or(0, |m| m.version);
+        migrations.extend(MIGRATIONS.iter().filter(|m| m.version > version).map(|m| {
+            SchemaMigration {
+                version: m.version,
+                description: m.description.to_string(),
+                applied_at: None,
+            }
+        }));
+        Ok(SchemaInfo {
+            version,
+            latest: MIGRATIONS.last().map_or(0, |m| m.version),
+            migrations,
+        })
     }
 }
 
+/// The last migration applied to the database, zero if none.
+fn schema_version(conn: &Connection) -> StorageResult<u32> {
+    Ok(conn.query_row(
+        "SELECT COALESCE(MAX(version), 0) FROM schema_migrations",
+        [],
+        |row| row.get(0),
+    )?)
+}
+
 #[async_trait::async_trait]
 impl Storage for SqliteStorage {
-    fn insert_test_node(&self, node: &TestNode) {
+    fn insert_run(&self, manifest: &str) -> StorageResult<i64> {
+        let conn = self.conn();
+        conn.execute("INSERT INTO runs (manifest) VALUES (?1)", (manifest,))?;
+        Ok(conn.last_insert_rowid())
+    }
+
+    fn finish_run(&self, run: &DbRun) -> StorageResult<()> {
+        let conn = self.conn();
+        conn.execute(
+            "UPDATE runs SET
+                finished_at = STRFTIME('%Y-%m-%d %H:%M:%f', 'NOW'),
+                completed = ?2, failed = ?3, assertion_failed = ?4,
+                skipped = ?5, cancelled = ?6, total = ?7
+            WHERE id = ?1",
+            (
+                run.id,
+                run.completed,
+                run.failed,
+                run.assertion_failed,
+                run.skipped,
+                run.cancelled,
+                run.total,
+            ),
+        )?;
+        Ok(())
+    }
+
+    fn get_runs(&self) -> StorageResult<Vec<DbRun>> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare(
+            "SELECT id, manifest, started_at, finished_at, completed, failed,
+                assertion_failed, skipped, cancelled, total
+            FROM runs ORDER BY id ASC",
+        )?;
+        let run_iter = stmt.query_map([], |row| {
+            Ok(DbRun {
+                id: row.get(0)?,
+                manifest: row.get(1)?,
+                started_at: row.get(2)?,
+                finished_at: row.get(3)?,
+                completed: row.get(4)?,
+                failed: row.get(5)?,
+                assertion_failed: row.get(6)?,
+                skipped: row.get(7)?,
+                cancelled: row.get(8)?,
+                total: row.get(9)?,
+            })
+        })?;
+        Ok(run_iter.collect::<rusqlite::Result<_>>()?)
+    }
+
+    fn get_last_run(&self) -> StorageResult<Option<DbRun>> {
+        Ok(self.get_runs()?.pop())
+    }
+
+    fn insert_test_node(&self, run: i64, node: &TestNode) -> StorageResult<()> {
         let dbnode = node.clone().into();
         let status = node.last_status();
-        let node_id = self.insert_node(dbnode);
+        let node_id = self.insert_node(run, dbnode)?;
         self.insert_node_history(
+            run,
             &status.to_string(),
             node_id,
             &node.executable.output.clone().unwrap_or_default(),
-        );
+        )?;
+        Ok(())
     }
 
-    fn insert_node(&self, node: DbNode) -> i64 {
+    fn insert_node(&self, run: i64, node: DbNode) -> StorageResult<i64> {
         let conn = self.conn();
         conn.execute(
-            "INSERT INTO nodes (id, test_id, name, description, service)
-                VALUES (?1, ?2, ?3, ?4, ?5)",
+            "INSERT INTO nodes (run, id, test_id, name, description, service)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
             (
+                run,
                 node.id,
                 node.test_id,
                 node.name,
                 node.description,
                 node.service,
             ),
-        )
-        .unwrap();
-        conn.last_insert_rowid()
+        )?;
+        Ok(node.id as i64)
     }
 
-    fn insert_node_history(&self, status: &str, node_id: i64, data: &str) -> i64 {
+    fn insert_node_history(
+        &self,
+        run: i64,
+        status: &str,
+        node_id: i64,
+        data: &str,
+    ) -> StorageResult<i64> {
         let conn = self.conn();
         conn.execute(
-            "INSERT INTO node_history (status, node, data) VALUES (?1, ?2, ?3)",
-            (status, node_id, data),
-        )
-        .unwrap();
-        conn.last_insert_rowid()
+            "INSERT INTO node_history (run, status, node, data) VALUES (?1, ?2, ?3, ?4)",
+            (run, status, node_id, data),
+        )?;
+        Ok(conn.last_insert_rowid())
     }
 
-    fn insert_dot(&self, dot: &str) -> i64 {
+    fn insert_dot(&self, run: i64, dot: &str) -> StorageResult<i64> {
         let conn = self.conn();
-        conn.execute("INSERT INTO graph (dot) VALUES (?1)", (dot,))
-            .unwrap();
-        conn.last_insert_rowid()
+        conn.execute("INSERT INTO graph (run, dot) VALUES (?1, ?2)", (run, dot))?;
+        Ok(conn.last_insert_rowid())
     }
 
-    fn get_nodes(&self, ids: &[i32]) -> Result<Vec<DbNode>> {
+    fn get_nodes(&self, run: i64, ids: &[i32]) -> StorageResult<Vec<DbNode>> {
         let conn = self.conn();
-        rusqlite::vtab::array::load_module(&conn)?;
         let values = Rc::new(
             ids.iter()
                 .copied()
@@ -118,9 +289,9 @@ impl Storage for SqliteStorage {
                 .collect::<Vec<rusqlite::types::Value>>(),
         );
         let mut stmt = conn.prepare(
-            "SELECT id, test_id, name, description, service FROM nodes WHERE id IN rarray(?1) ORDER BY id ASC"
+            "SELECT id, test_id, name, description, service FROM nodes WHERE run = ?1 AND id IN rarray(?2) ORDER BY id ASC"
         )?;
-        let node_iter = stmt.query_map([values], |row| {
+        let node_iter = stmt.query_map((run, values), |row| {
             Ok(DbNode {
                 id: row.get(0)?,
                 test_id: row.get(1)?,
@@ -129,14 +300,14 @@ impl Storage for SqliteStorage {
                 service: row.get(4)?,
             })
         })?;
-        node_iter.collect()
+        Ok(node_iter.collect::<rusqlite::Result<_>>()?)
     }
-    fn get_all_nodes(&self) -> Result<Vec<DbNode>> {
+    fn get_all_nodes(&self, run: i64) -> StorageResult<Vec<DbNode>> {
         let conn = self.conn();
         let mut stmt = conn.prepare(
-            "SELECT id, test_id, name, description, service FROM nodes ORDER BY id ASC"
+            "SELECT id, test_id, name, description, service FROM nodes WHERE run = ?1 ORDER BY id ASC"
         )?;
-        let node_iter = stmt.query_map([], |row| {
+        let node_iter = stmt.query_map([run], |row| {
             Ok(DbNode {
                 id: row.get(0)?,
                 test_id: row.get(1)?,
@@ -145,14 +316,14 @@ impl Storage for SqliteStorage {
                 service: row.get(4)?,
             })
         })?;
-        node_iter.collect()
+        Ok(node_iter.collect::<rusqlite::Result<_>>()?)
     }
 
-    fn get_node_history(&self, node_id: i32) -> Result<Vec<NodeHistory>> {
+    fn get_node_history(&self, run: i64, node_id: i32) -> StorageResult<Vec<NodeHistory>> {
         let conn = self.conn();
         let mut stmt =
-            conn.prepare("SELECT id, status, node, data, created_at FROM node_history WHERE node = ?1 ORDER BY created_at ASC")?;
-        let history_iter = stmt.query_map([node_id], |row| {
+            conn.prepare("SELECT id, status, node, data, created_at FROM node_history WHERE run = ?1 AND node = ?2 ORDER BY created_at ASC")?;
+        let history_iter = stmt.query_map((run, node_id), |row| {
             Ok(NodeHistory {
                 id: row.get(0)?,
                 status: row.get(1)?,
@@ -161,14 +332,18 @@ impl Storage for SqliteStorage {
                 created_at: row.get(4)?,
             })
         })?;
-        history_iter.collect()
+        Ok(history_iter.collect::<rusqlite::Result<_>>()?)
     }
 
-    fn get_processed_node_history(&self, node_id: i32) -> Result<Vec<ProcessedHistory>> {
+    fn get_processed_node_history(
+        &self,
+        run: i64,
+        node_id: i32,
+    ) -> StorageResult<Vec<ProcessedHistory>> {
         let conn = self.conn();
         let mut stmt = conn.prepare(HISTORY_WITH_DURATION_BETWEEN_STATUS)?;
         let history_iter = stmt
-            .query_map([node_id], |row| {
+            .query_map((run, node_id), |row| {
                 Ok(ProcessedHistory {
                     node: row.get(0)?,
                     from_status: row.get(1).unwrap_or_default(),
@@ -178,7 +353,7 @@ impl Storage for SqliteStorage {
                     duration_millis: row.get(5).unwrap_or_default(),
                 })
             })?
-            .collect::<Result<Vec<ProcessedHistory>>>()?;
+            .collect::<rusqlite::Result<Vec<ProcessedHistory>>>()?;
         // filter out empty to_status
         // it means that the from_status is the last status provided.
         Ok(history_iter
@@ -187,11 +362,11 @@ impl Storage for SqliteStorage {
             .collect())
     }
 
-    fn get_all_processed_node_history(&self) -> Result<Vec<ProcessedHistory>> {
+    fn get_all_processed_node_history(&self, run: i64) -> StorageResult<Vec<ProcessedHistory>> {
         let conn = self.conn();
         let mut stmt = conn.prepare(ALL_HISTORY_WITH_DURATION_BETWEEN_STATUS)?;
         let history_iter = stmt
-            .query_map([], |row| {
+            .query_map([run], |row| {
                 Ok(ProcessedHistory {
                     node: row.get(0)?,
                     from_status: row.get(1).unwrap_or_default(),
@@ -201,7 +376,7 @@ impl Storage for SqliteStorage {
                     duration_millis: row.get(5).unwrap_or_default(),
                 })
             })?
-            .collect::<Result<Vec<ProcessedHistory>>>()?;
+            .collect::<rusqlite::Result<Vec<ProcessedHistory>>>()?;
         // filter out empty to_status
         // it means that the from_status is the last status provided.
         Ok(history_iter
@@ -210,21 +385,22 @@ impl Storage for SqliteStorage {
             .collect())
     }
 
-    fn get_dots(&self) -> Result<Vec<DbGraph>> {
+    fn get_dots(&self, run: i64) -> StorageResult<Vec<DbGraph>> {
         let conn = self.conn();
-        let mut stmt = conn.prepare("SELECT id, dot, created_at FROM graph")?;
-        let graph_iter = stmt.query_map([], |row| {
+        let mut stmt = conn.prepare("SELECT id, dot, created_at FROM graph WHERE run = ?1")?;
+        let graph_iter = stmt.query_map([run], |row| {
             Ok(DbGraph {
                 id: row.get(0)?,
                 dot: row.get(1)?,
                 created_at: row.get(2)?,
             })
         })?;
-        graph_iter.collect()
+        Ok(graph_iter.collect::<rusqlite::Result<_>>()?)
     }
-    fn insert_test_nodes(&self, nodes: Vec<&TestNode>) {
+    fn insert_test_nodes(&self, run: i64, nodes: Vec<&TestNode>) -> StorageResult<()> {
         for node in nodes {
-            self.insert_test_node(node);
+            self.insert_test_node(run, node)?;
         }
+        Ok(())
     }
 }
@@ -9,14 +9,15 @@ SELECT
 FROM (
   SELECT
     id,
+    run,
     node,
     status AS from_status,
     created_at,
-    (SELECT created_at FROM node_history WHERE strftime('%s', created_at) > strftime('%s', nh.created_at) and node = nh.node ORDER BY created_at ASC LIMIT 1) AS next_created_at,
-    (SELECT status FROM node_history WHERE strftime('%s', created_at) > strftime('%s', nh.created_at) and node = nh.node ORDER BY created_at ASC LIMIT 1) AS to_status
+    (SELECT created_at FROM node_history WHERE strftime('%s', created_at) > strftime('%s', nh.created_at) and node = nh.node and run = nh.run ORDER BY created_at ASC LIMIT 1) AS next_created_at,
+    (SELECT status FROM node_history WHERE strftime('%s', created_at) > strftime('%s', nh.created_at) and node = nh.node and run = nh.run ORDER BY created_at ASC LIMIT 1) AS to_status
   FROM
     node_history AS nh
-) AS OrderedHistory WHERE node = ?1;";
+) AS OrderedHistory WHERE run = ?1 AND node = ?2;";
 
 pub const ALL_HISTORY_WITH_DURATION_BETWEEN_STATUS: &str = "
 SELECT
@@ -29,11 +30,12 @@ SELECT
 FROM (
   SELECT
     id,
+    run,
     node,
     status AS from_status,
     created_at,
-    (SELECT created_at FROM node_history WHERE strftime('%s', created_at) > strftime('%s', nh.created_at) and node = nh.node ORDER BY created_at ASC LIMIT 1) AS next_created_at,
-    (SELECT status FROM node_history WHERE strftime('%s', created_at) > strftime('%s', nh.created_at) and node = nh.node ORDER BY created_at ASC LIMIT 1) AS to_status
+    (SELECT created_at FROM node_history WHERE strftime('%s', created_at) > strftime('%s', nh.created_at) and node = nh.node and run = nh.run ORDER BY created_at ASC LIMIT 1) AS next_created_at,
+    (SELECT status FROM node_history WHERE strftime('%s', created_at) > strftime('%s', nh.created_at) and node = nh.node and run = nh.run ORDER BY created_at ASC LIMIT 1) AS to_status
   FROM
     node_history AS nh
-) AS OrderedHistory";
+) AS OrderedHistory WHERE run = ?1";
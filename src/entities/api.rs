@@ -11,4 +11,6 @@ pub struct TestNodeInfo {
     pub service: String,
     pub history: Vec<ProcessedHistory>,
     pub data: String,
+    /// The test has completed, but only after being retried.
+    pub flaky: bool,
 }
@@ -4,26 +4,32 @@ use petgraph::prelude::*;
 
 use crate::traits::Manifest;
 
-use super::{graph::TestNode, manifests::BaseManifest, storage::DbNode};
+use super::{
+    enums::DependencyCondition, graph::TestNode, manifests::BaseManifest, storage::DbNode,
+};
 
 pub fn new_uuidv4() -> String {
     uuid::Uuid::new_v4().to_string()
 }
 
-/// Checks if the depends_on clause has valid ids (the id must exists).
-pub fn checks_depends_on(nodes: &Vec<TestNode>) -> Result<()> {
+/// Checks if the depends_on clause has valid ids (all the ids must exist).
+pub fn checks_depends_on(nodes: &[TestNode]) -> Result<()> {
     let mut ids: Vec<String> = Vec::new();
     for node in nodes.iter() {
         ids.push(node.id.clone());
     }
     for node in nodes.iter() {
-        if node.depends_on.is_empty() {
-            continue;
-        };
-        if !node.depends_on.iter().any(|x| ids.contains(x)) {
+        let missing = node
+            .depends_on
+            .iter()
+            .filter(|x| !ids.contains(x))
+            .cloned()
+            .collect::<Vec<String>>();
+        if !missing.is_empty() {
             return Err(anyhow::anyhow!(
-                "The test id '{}' has dependencies that does not exist!",
-                node.id
+                "The test id '{}' has dependencies that does not exist: {}!",
+                node.id,
+                missing.join(", ")
             ));
         }
     }
@@ -36,7 +42,7 @@ pub fn to_grpcurl_command(
     address: &str,
     method: &str,
 ) -> String {
-    let body = unescape::unescape(&body).unwrap();
+    let body = unescape::unescape(body).unwrap();
     let headers = match &headers {
         Some(headers) => headers
             .iter()
@@ -58,8 +64,8 @@ pub fn to_grpcurl_command(
     )
 }
 
-pub fn build_graph(test_nodes: Vec<TestNode>) -> DiGraph<TestNode, usize> {
-    let mut graph = DiGraph::<TestNode, usize>::new();
+pub fn build_graph(test_nodes: Vec<TestNode>) -> DiGraph<TestNode, DependencyCondition> {
+    let mut graph = DiGraph::<TestNode, DependencyCondition>::new();
     test_nodes.iter().for_each(|node| {
         graph.add_node(node.clone());
     });
@@ -69,14 +75,18 @@ pub fn build_graph(test_nodes: Vec<TestNode>) -> DiGraph<TestNode, usize> {
             graph.add_edge(
                 NodeIndex::new(tdep.index as usize),
                 NodeIndex::new(node.index as usize),
-                0,
+                node.executable
+                    .conditions
+                    .get(dep)
+                    .cloned()
+                    .unwrap_or_default(),
             );
         });
     }
     graph
 }
 
-impl TryFrom<BaseManifest> for DiGraph<TestNode, usize> {
+impl TryFrom<BaseManifest> for DiGraph<TestNode, DependencyCondition> {
     type Error = anyhow::Error;
 
     fn try_from(value: BaseManifest) -> Result<Self, Self::Error> {
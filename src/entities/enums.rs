@@ -13,14 +13,79 @@ pub enum TestStatus {
     Failed,
     // When the test fails during the response checks specified
     AssertionFailed,
+    // When the test attempt failed and it will be retried
+    Retrying,
+    // When the run was cancelled before the test has finished
+    Cancelled,
     // When the test has been skipped
     // Commonly is used when the test depends on another test that has failed
     Skipped,
 }
 
+impl TestStatus {
+    /// The test has reached a status that won't change anymore during the run.
+    pub fn is_final(&self) -> bool {
+        matches!(
+            self,
+            TestStatus::Completed
+                | TestStatus::Failed
+                | TestStatus::AssertionFailed
+                | TestStatus::Skipped
+                | TestStatus::Cancelled
+        )
+    }
+}
+
+/// When a test runs, given the final status of one of its dependencies.
+///
+/// It is the weight of the graph edges.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Display, EnumString, Serialize, Deserialize,
+)]
+#[strum(serialize_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum DependencyCondition {
+    /// Only if the dependency has completed
+    #[default]
+    Success,
+    /// Only if the dependency has failed (Failed or AssertionFailed)
+    Failure,
+    /// Whatever the dependency final status is, unless it was cancelled
+    Always,
+}
+
+impl DependencyCondition {
+    /// Checks the condition over the dependency status,
+    /// None if the dependency has not reached a final status yet.
+    pub fn is_satisfied(&self, status: &TestStatus) -> Option<bool> {
+        if !status.is_final() {
+            return None;
+        }
+        Some(match self {
+            DependencyCondition::Success => *status == TestStatus::Completed,
+            DependencyCondition::Failure => {
+                matches!(status, TestStatus::Failed | TestStatus::AssertionFailed)
+            }
+            // a cancelled run doesn't go on, but the teardowns do (see `availables`)
+            DependencyCondition::Always => *status != TestStatus::Cancelled,
+        })
+    }
+}
+
+/// The special nodes that prepare and clean up a service (or the whole run) around its tests.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Display, EnumString, Serialize, Deserialize)]
+#[strum(serialize_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum HookKind {
+    /// Runs before the tests, they are skipped if it doesn't complete
+    Setup,
+    /// Runs after all the tests have reached a final status, whatever it is
+    Teardown,
+}
+
 /// Enum ManifestKind,
 /// defines which manifest parser to use
-#[derive(Debug, Clone, Default, PartialEq, EnumString, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, PartialEq, Display, EnumString, Serialize, Deserialize)]
 #[strum(serialize_all = "lowercase")]
 pub enum ManifestKind {
     Grpc,
@@ -28,6 +93,19 @@ pub enum ManifestKind {
     Scripts,
 }
 
+/// The bundled storage backends, see `db::open_storage`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Display, EnumString)]
+#[strum(serialize_all = "lowercase")]
+pub enum StorageKind {
+    /// A SQLite database file
+    #[default]
+    Sqlite,
+    /// A directory with a JSON lines file per table
+    Jsonl,
+    /// Nothing is written, the history is lost at exit
+    Memory,
+}
+
 /// Enum ExtType
 #[derive(Debug, Clone, PartialEq, EnumString, Serialize, Deserialize)]
 #[strum(serialize_all = "lowercase")]
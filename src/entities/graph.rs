@@ -1,15 +1,33 @@
 use anyhow::Result;
+use regex::Regex;
 use serde::{Deserialize, Serialize};
-use std::fmt::{Debug, Display};
+use std::{
+    collections::HashMap,
+    fmt::{Debug, Display},
+    time::Duration,
+};
 
-use crate::services::test_executable::{grpc_call, scripts_call};
+use crate::services::{
+    templates::render,
+    test_executable::{grpc_call, scripts_call},
+};
 
-use super::enums::{ManifestKind, TestStatus};
+use super::{
+    enums::{DependencyCondition, HookKind, ManifestKind, TestStatus},
+    manifests::{ReadyProbe, ReqSpec, RetryOn},
+    validation::Location,
+};
 
+#[derive(Debug, Clone, Default)]
 pub struct FilterOptions {
     pub id: Option<String>,
     pub status: Option<TestStatus>,
     pub index: Option<u32>,
+    /// Matches the tests with any of these tags
+    pub tags: Vec<String>,
+    pub service: Option<String>,
+    /// Matches the ids with any of these glob patterns, e.g: `foo.*` or `*.test?`
+    pub globs: Vec<String>,
 }
 
 #[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
@@ -22,6 +40,60 @@ pub struct TestExecutable {
     pub output: Option<String>,
     pub exit_code: Option<i32>,
     pub kind: ManifestKind,
+    /// The gRPC request to perform, only present for `ManifestKind::Grpc` tests.
+    pub grpc: Option<GrpcRequest>,
+    /// The expected results, checked after the call.
+    pub expect: Option<ReqSpec>,
+    /// Why the test reached its last status, e.g: an assertion mismatch.
+    pub reason: Option<String>,
+    /// Maximum duration of the call, the test fails as timed out after that.
+    #[serde(default, with = "humantime_serde")]
+    pub timeout: Option<Duration>,
+    /// How many times the test is retried after a failure.
+    #[serde(default)]
+    pub retries: u32,
+    /// The delay before the first retry, it doubles on each retry (exponential backoff).
+    #[serde(default, with = "humantime_serde")]
+    pub retry_delay: Option<Duration>,
+    /// Which failures are retried, all of them if not present.
+    pub retry_on: Option<RetryOn>,
+    /// Maximum number of tests from the same service running at the same time.
+    pub max_parallel: Option<usize>,
+    /// Named resources used by the test, tests sharing a lock never run at the same time.
+    #[serde(default)]
+    pub locks: Vec<String>,
+    /// The test runs alone, no other test runs at the same time.
+    #[serde(default)]
+    pub exclusive: bool,
+    /// The condition of each dependency (by test id), `success` if not present.
+    #[serde(default)]
+    pub conditions: HashMap<String, DependencyCondition>,
+    /// Labels used to select the tests to run, e.g: `smoke`
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Present if the node is a setup or teardown hook instead of a test.
+    #[serde(default)]
+    pub hook: Option<HookKind>,
+    /// Present if the node is a daemon, it is ready (Completed) once the probe succeeds.
+    #[serde(default)]
+    pub daemon: Option<ReadyProbe>,
+    /// Where the test was declared
+    pub location: Location,
+}
+
+/// All the information needed to perform a unary gRPC call.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct GrpcRequest {
+    /// Server address, e.g: `localhost:50051` or `http://localhost:50051`
+    pub address: String,
+    /// Path of the proto file that declares the service
+    pub proto: String,
+    /// Full method name, e.g: `helloworld.Greeter/SayHello`
+    pub method: String,
+    /// The request message in JSON format
+    pub body: String,
+    /// Metadata sent with the request, in the `key: value` format
+    pub headers: Vec<String>,
 }
 
 impl TestExecutable {
@@ -32,6 +104,28 @@ impl TestExecutable {
         };
         Ok(self.output.clone().unwrap_or_default())
     }
+
+    /// All the texts that accept templates: the command, the gRPC body and headers.
+    pub fn templates(&self) -> Vec<&String> {
+        let mut templates = vec![&self.command];
+        if let Some(grpc) = &self.grpc {
+            templates.push(&grpc.body);
+            templates.extend(grpc.headers.iter());
+        }
+        templates
+    }
+
+    /// Replaces the templates placeholders with the results of the tests in the context.
+    pub fn render_templates(&mut self, context: &HashMap<String, &TestNode>) -> Result<()> {
+        self.command = render(&self.command, context)?;
+        if let Some(grpc) = &mut self.grpc {
+            grpc.body = render(&grpc.body, context)?;
+            for header in grpc.headers.iter_mut() {
+                *header = render(header, context)?;
+            }
+        }
+        Ok(())
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -59,77 +153,112 @@ impl TestNode {
             .cloned()
             .unwrap_or(TestStatus::NotStarted)
     }
+
+    /// Adds a dependency, unless the test already depends on it.
+    pub fn add_dependency(&mut self, id: &str, on: DependencyCondition) {
+        if !self.depends_on.iter().any(|dep| dep == id) {
+            self.depends_on.push(id.to_string());
+            self.executable.conditions.insert(id.to_string(), on);
+        }
+    }
+
+    /// A flaky test has completed, but only after being retried.
+    pub fn is_flaky(&self) -> bool {
+        self.last_status() == TestStatus::Completed && self.status.contains(&TestStatus::Retrying)
+    }
+}
+
+/// Matches the text with a glob pattern, where `*` matches any text and `?` any character.
+pub fn glob_matches(glob: &str, text: &str) -> bool {
+    let pattern = regex::escape(glob).replace("\\*", ".*").replace("\\?", ".");
+    Regex::new(&format!("^{}$", pattern))
+        .map(|re| re.is_match(text))
+        .unwrap_or(false)
 }
 
 impl FilterOptions {
     /// Check if the node matches the filter options.
     ///
-    /// If an empty filter is provided (all FilterOptions as None), it will match all nodes.
+    /// All the present fields must match, if an empty filter is provided
+    /// (all FilterOptions as None or empty), it will match all nodes.
     ///
     /// In other hands, an empty filter is completelly permissive.
     pub fn check(&self, node: &TestNode) -> bool {
-        if let Some(index) = self.index {
-            return node.index == index;
-        };
-        if let Some(id) = &self.id {
-            return node.id == *id;
-        };
-        if let Some(status) = &self.status {
-            return &node.last_status() == status;
-        };
-        true
+        self.index.is_none_or(|index| node.index == index)
+            && self.id.as_ref().is_none_or(|id| node.id == *id)
+            && self
+                .status
+                .is_none_or(|status| node.last_status() == status)
+            && self
+                .service
+                .as_ref()
+                .is_none_or(|service| node.executable.service == *service)
+            && (self.tags.is_empty()
+                || self
+                    .tags
+                    .iter()
+                    .any(|tag| node.executable.tags.contains(tag)))
+            && (self.globs.is_empty() || self.globs.iter().any(|glob| glob_matches(glob, &node.id)))
     }
+
     /// Basic filter that matches Completed nodes
     pub fn completed() -> Self {
         Self {
-            id: None,
             status: Some(TestStatus::Completed),
-            index: None,
+            ..Default::default()
         }
     }
 
     /// Basic filter that matches NotStarted nodes
     pub fn not_started() -> Self {
         Self {
-            id: None,
             status: Some(TestStatus::NotStarted),
-            index: None,
+            ..Default::default()
         }
     }
 
     /// Basic filter that matches Failed nodes
     pub fn failed() -> Self {
         Self {
-            id: None,
             status: Some(TestStatus::Failed),
-            index: None,
+            ..Default::default()
+        }
+    }
+
+    /// Basic filter that matches AssertionFailed nodes
+    pub fn assertion_failed() -> Self {
+        Self {
+            status: Some(TestStatus::AssertionFailed),
+            ..Default::default()
         }
     }
 
     /// Basic filter that matches Skipped nodes
     pub fn skipped() -> Self {
         Self {
-            id: None,
             status: Some(TestStatus::Skipped),
-            index: None,
+            ..Default::default()
+        }
+    }
+
+    /// Basic filter that matches Cancelled nodes
+    pub fn cancelled() -> Self {
+        Self {
+            status: Some(TestStatus::Cancelled),
+            ..Default::default()
         }
     }
 
     /// Basic filter that matches Running nodes
     pub fn running() -> Self {
         Self {
-            id: None,
             status: Some(TestStatus::Running),
-            index: None,
+            ..Default::default()
         }
     }
 
     /// Basic filter that matches all nodes
     pub fn all() -> Self {
-        Self {
-            id: None,
-            status: None,
-            index: None,
-        }
+        Self::default()
     }
 }
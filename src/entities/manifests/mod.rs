@@ -1,6 +1,6 @@
-use std::{iter::Sum, ops::Add};
+use std::{iter::Sum, ops::Add, time::Duration};
 
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 
 use anyhow::Result;
 
@@ -8,7 +8,12 @@ use crate::traits::Manifest;
 
 use self::{grpc::MGrpcFile, scripts::MScriptFile};
 
-use super::{conversions::checks_depends_on, graph::TestNode};
+use super::{
+    conversions::{checks_depends_on, new_uuidv4},
+    enums::{DependencyCondition, HookKind, ManifestKind, TestStatus},
+    graph::{TestExecutable, TestNode},
+    validation::{validate_services, validate_test_nodes, Location, ValidationErrors},
+};
 
 pub mod grpc;
 pub mod scripts;
@@ -19,27 +24,304 @@ pub struct BaseManifest {
     pub grpc: Option<MGrpcFile>,
 }
 
+/// The expected results of a test, checked after its execution.
+#[derive(Debug, Default, Serialize, Deserialize, Eq, PartialEq, Clone)]
+pub struct ReqSpec {
+    /// The exit code for scripts or the gRPC status (code or name, e.g: `5` or `NotFound`) for grpc.
+    #[serde(default, deserialize_with = "deserialize_string_or_number")]
+    pub status: Option<String>,
+    pub output: Option<String>,
+}
+
+/// A `depends_on` entry, the test id alone (e.g: `test1`)
+/// or with the condition to run (e.g: `{id: test1, on: failure}`).
+#[derive(Debug, Clone, Serialize, Eq, PartialEq)]
+pub struct Dependency {
+    pub id: String,
+    pub on: DependencyCondition,
+}
+
+impl<'de> Deserialize<'de> for Dependency {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum IdOrDependency {
+            Id(String),
+            Dependency {
+                id: String,
+                #[serde(default)]
+                on: DependencyCondition,
+            },
+        }
+        Ok(match IdOrDependency::deserialize(deserializer)? {
+            IdOrDependency::Id(id) => Dependency {
+                id,
+                on: DependencyCondition::default(),
+            },
+            IdOrDependency::Dependency { id, on } => Dependency { id, on },
+        })
+    }
+}
+
+/// A command that prepares (`setup`) or cleans up (`teardown`) the environment of the tests,
+/// declared for a service or at the manifest root for the whole run.
+#[derive(Debug, Default, Serialize, Deserialize, Eq, PartialEq, Clone)]
+pub struct Hook {
+    pub command: String,
+    pub description: Option<String>,
+    /// The hook timeout, it overrides the service timeout
+    #[serde(default, with = "humantime_serde")]
+    pub timeout: Option<Duration>,
+    #[serde(skip)]
+    pub location: Location,
+}
+
+impl Hook {
+    /// Converts the hook into a scripts node, whatever the manifest kind is.
+    ///
+    /// The node id is `<service>.<kind>`, e.g: `foo.setup`, or only the kind for the run hooks.
+    pub fn as_test_node(&self, kind: HookKind, service: Option<&str>) -> TestNode {
+        let (id, description) = match service {
+            Some(service) => (
+                format!("{}.{}", service, kind),
+                format!("The {} of the service '{}'", kind, service),
+            ),
+            None => (kind.to_string(), format!("The {} of the run", kind)),
+        };
+        TestNode {
+            id: id.clone(),
+            index: 0,
+            status: vec![TestStatus::NotStarted],
+            depends_on: vec![],
+            executable: TestExecutable {
+                id,
+                name: kind.to_string(),
+                service: service.unwrap_or_default().to_string(),
+                command: self.command.clone(),
+                description: self.description.clone().unwrap_or(description),
+                kind: ManifestKind::Scripts,
+                timeout: self.timeout,
+                hook: Some(kind),
+                location: self.location.clone(),
+                ..Default::default()
+            },
+        }
+    }
+}
+
+/// A long-lived process started in background, e.g: a local server used by the tests.
+///
+/// It is ready once its probe succeeds, then it keeps running until the end of the run.
+#[derive(Debug, Serialize, Deserialize, Eq, PartialEq, Clone)]
+pub struct Daemon {
+    pub name: String,
+    #[serde(default = "new_uuidv4")]
+    pub id: String,
+    pub command: String,
+    pub description: Option<String>,
+    /// How to know the daemon is ready to be used
+    pub ready: ReadyProbe,
+    /// Maximum time to wait for the daemon to be ready, it overrides the service timeout
+    #[serde(default, with = "humantime_serde")]
+    pub timeout: Option<Duration>,
+    #[serde(skip)]
+    pub location: Location,
+}
+
+/// The check that tells when a daemon is ready, e.g: `ready: {tcp: localhost:3000}`
+#[derive(Debug, Serialize, Deserialize, Eq, PartialEq, Clone)]
+#[serde(rename_all = "lowercase")]
+pub enum ReadyProbe {
+    /// The address accepts TCP connections, e.g: `localhost:3000`
+    Tcp(String),
+    /// A plain HTTP GET on the URL responds with 200, e.g: `http://localhost:3000/health`
+    Http(String),
+    /// A regex matching a line printed by the daemon, e.g: `Listening on`
+    Log(String),
+}
+
+impl Daemon {
+    /// Converts the daemon into a scripts node, with the `<service>.<id>` id once normalized.
+    pub fn as_test_node(&self, service: &str) -> TestNode {
+        TestNode {
+            id: self.id.clone(),
+            index: 0,
+            status: vec![TestStatus::NotStarted],
+            depends_on: vec![],
+            executable: TestExecutable {
+                id: self.id.clone(),
+                name: self.name.clone(),
+                service: service.to_string(),
+                command: self.command.clone(),
+                description: self
+                    .description
+                    .clone()
+                    .unwrap_or_else(|| format!("The daemon '{}'", self.name)),
+                kind: ManifestKind::Scripts,
+                timeout: self.timeout,
+                daemon: Some(self.ready.clone()),
+                location: self.location.clone(),
+                ..Default::default()
+            },
+        }
+    }
+}
+
+/// Wires the setup and teardown nodes around the tests:
+/// * the tests depend on the setup, so they are skipped if the setup doesn't complete.
+/// * the teardown depends on the setup and all the tests with the `always` condition,
+///   so it runs once all of them have reached a final status.
+pub fn with_hooks(
+    mut nodes: Vec<TestNode>,
+    setup: Option<TestNode>,
+    teardown: Option<TestNode>,
+) -> Vec<TestNode> {
+    if let Some(setup) = setup {
+        for node in nodes.iter_mut() {
+            node.add_dependency(&setup.id, DependencyCondition::Success);
+        }
+        nodes.insert(0, setup);
+    }
+    if let Some(mut teardown) = teardown {
+        for node in nodes.iter() {
+            teardown.add_dependency(&node.id, DependencyCondition::Always);
+        }
+        nodes.push(teardown);
+    }
+    nodes
+}
+
+/// Sets the locations of the daemons of the service `i`, declared as `daemons` or `background`.
+pub fn set_daemons_locations(daemons: &mut [Daemon], i: usize, locate: &impl Fn(&str) -> Location) {
+    for (j, daemon) in daemons.iter_mut().enumerate() {
+        daemon.location = locate(&format!("services[{}].daemons[{}]", i, j));
+        if daemon.location.line == 0 {
+            daemon.location = locate(&format!("services[{}].background[{}]", i, j));
+        }
+    }
+}
+
+/// Formats the daemons ids as <service>.<daemon_id>, like the tests ids.
+pub fn format_daemons_ids(daemons: &mut [Daemon], service: &str) {
+    for daemon in daemons.iter_mut() {
+        daemon.id = format!("{}.{}", service, daemon.id);
+    }
+}
+
+/// The daemons are nodes like the tests, wrapped by the service hooks as well,
+/// so the service tests nodes start with them.
+pub fn daemons_as_test_nodes(
+    daemons: &[Daemon],
+    service: &str,
+    timeout: Option<Duration>,
+) -> Vec<TestNode> {
+    daemons
+        .iter()
+        .map(|daemon| {
+            let mut node = daemon.as_test_node(service);
+            node.executable.timeout = daemon.timeout.or(timeout);
+            node
+        })
+        .collect()
+}
+
+/// Conditions to retry a failed test, matching any of them is enough.
+///
+/// Without conditions, any failure is retried.
+#[derive(Debug, Default, Serialize, Deserialize, Eq, PartialEq, Clone)]
+pub struct RetryOn {
+    /// The exit codes for scripts or the gRPC status codes for grpc.
+    #[serde(default)]
+    pub exit_codes: Vec<i32>,
+    /// A regex searched in the test output
+    pub output: Option<String>,
+}
+
+/// The declared hooks, as (kind, hook) pairs.
+pub fn hooks_of<'a>(
+    setup: &'a Option<Hook>,
+    teardown: &'a Option<Hook>,
+) -> Vec<(HookKind, &'a Hook)> {
+    [(HookKind::Setup, setup), (HookKind::Teardown, teardown)]
+        .into_iter()
+        .filter_map(|(kind, hook)| hook.as_ref().map(|hook| (kind, hook)))
+        .collect()
+}
+
+/// Allows yaml/json numbers where a string is expected, e.g: `status: 0`
+fn deserialize_string_or_number<'de, D>(deserializer: D) -> Result<Option<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum StringOrNumber {
+        String(String),
+        Number(i64),
+    }
+    Ok(
+        Option::<StringOrNumber>::deserialize(deserializer)?.map(|value| match value {
+            StringOrNumber::String(s) => s,
+            StringOrNumber::Number(n) => n.to_string(),
+        }),
+    )
+}
+
 impl BaseManifest {
     pub fn new(scripts: Option<MScriptFile>, grpc: Option<MGrpcFile>) -> Self {
         Self { scripts, grpc }
     }
-}
 
-impl Manifest for BaseManifest {
-    fn normalize(&mut self) -> Result<()> {
-        if let Some(scripts) = &mut self.scripts {
-            scripts.normalize()?;
-            self.scripts = Some(scripts.to_owned());
+    /// All the service names defined in the manifests, for all kinds.
+    pub fn service_names(&self) -> Vec<String> {
+        let mut names = vec![];
+        if let Some(scripts) = &self.scripts {
+            names.append(&mut scripts.service_names());
         }
-        if let Some(grpc) = &mut self.grpc {
-            grpc.normalize()?;
-            self.grpc = Some(grpc.to_owned());
+        if let Some(grpc) = &self.grpc {
+            names.append(&mut grpc.service_names());
         }
-        let _ = self.as_test_nodes()?;
-        Ok(())
+        names
     }
 
-    fn as_test_nodes(&self) -> Result<Vec<TestNode>> {
+    /// All the services names and locations, for all kinds.
+    pub fn service_locations(&self) -> Vec<(String, Location)> {
+        let mut services = vec![];
+        if let Some(scripts) = &self.scripts {
+            services.extend(
+                scripts
+                    .services
+                    .iter()
+                    .map(|s| (s.name.clone(), s.location.clone())),
+            );
+        }
+        if let Some(grpc) = &self.grpc {
+            services.extend(
+                grpc.services
+                    .iter()
+                    .map(|s| (s.name.clone(), s.location.clone())),
+            );
+        }
+        services
+    }
+
+    /// The run setup and teardown, declared at the root of the manifests.
+    pub fn root_hooks(&self) -> Vec<(HookKind, &Hook)> {
+        let mut hooks = vec![];
+        if let Some(scripts) = &self.scripts {
+            hooks.extend(hooks_of(&scripts.setup, &scripts.teardown));
+        }
+        if let Some(grpc) = &self.grpc {
+            hooks.extend(hooks_of(&grpc.setup, &grpc.teardown));
+        }
+        hooks
+    }
+
+    /// Converts the tests from all manifest kinds into TestNodes, without any integrity check.
+    fn collect_test_nodes(&self) -> Result<Vec<TestNode>> {
         let mut nodes = Vec::new();
         if let Some(scripts) = &self.scripts {
             nodes.append(&mut scripts.as_test_nodes()?);
@@ -47,6 +329,50 @@ impl Manifest for BaseManifest {
         if let Some(grpc) = &self.grpc {
             nodes.append(&mut grpc.as_test_nodes()?);
         }
+        // The run hooks wrap all the nodes, including the services hooks.
+        let hooks = self.root_hooks();
+        let hook_node = |kind: HookKind| {
+            hooks
+                .iter()
+                .find(|(k, _)| *k == kind)
+                .map(|(_, hook)| hook.as_test_node(kind, None))
+        };
+        let mut nodes = with_hooks(
+            nodes,
+            hook_node(HookKind::Setup),
+            hook_node(HookKind::Teardown),
+        );
+        // Each manifest kind indexes its own nodes starting from 0,
+        // the indexes are reassigned to be unique across all kinds.
+        nodes
+            .iter_mut()
+            .enumerate()
+            .for_each(|(index, node)| node.index = index as u32);
+        Ok(nodes)
+    }
+}
+
+impl Manifest for BaseManifest {
+    fn normalize(&mut self) -> Result<()> {
+        // A test can depend on tests from the other manifest kinds,
+        // so the ids are formatted with the service names from all of them.
+        let service_names = self.service_names();
+        if let Some(scripts) = &mut self.scripts {
+            scripts.format_test_ids(&service_names);
+        }
+        if let Some(grpc) = &mut self.grpc {
+            grpc.format_test_ids(&service_names);
+        }
+        // Collect all the problems at once instead of stopping at the first one
+        let mut errors = ValidationErrors::default();
+        validate_services(&self.service_locations(), &mut errors);
+        validate_test_nodes(&self.collect_test_nodes()?, &mut errors);
+        errors.into_result()?;
+        Ok(())
+    }
+
+    fn as_test_nodes(&self) -> Result<Vec<TestNode>> {
+        let nodes = self.collect_test_nodes()?;
         checks_depends_on(&nodes)?;
         Ok(nodes)
     }
@@ -56,23 +382,23 @@ impl Add for BaseManifest {
     type Output = Self;
 
     fn add(self, rhs: Self) -> Self::Output {
-        let scripts = match &rhs.scripts {
-            Some(other) => {
-                let mut services = vec![];
-                services.append(&mut other.services.to_vec());
-                services.append(&mut self.scripts.map(|x| x.services).unwrap_or_default());
-                Some(MScriptFile { services })
+        let scripts = match (self.scripts, rhs.scripts) {
+            (Some(mut current), Some(mut other)) => {
+                other.services.append(&mut current.services);
+                other.setup = other.setup.or(current.setup);
+                other.teardown = other.teardown.or(current.teardown);
+                Some(other)
             }
-            None => None,
+            (current, other) => other.or(current),
         };
-        let grpc = match &rhs.grpc {
-            Some(other) => {
-                let mut services = vec![];
-                services.append(&mut other.services.to_vec());
-                services.append(&mut self.grpc.map(|x| x.services).unwrap_or_default());
-                Some(MGrpcFile { services })
+        let grpc = match (self.grpc, rhs.grpc) {
+            (Some(mut current), Some(mut other)) => {
+                other.services.append(&mut current.services);
+                other.setup = other.setup.or(current.setup);
+                other.teardown = other.teardown.or(current.teardown);
+                Some(other)
             }
-            None => None,
+            (current, other) => other.or(current),
         };
         Self { scripts, grpc }
     }
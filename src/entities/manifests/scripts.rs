@@ -1,23 +1,52 @@
+use std::time::Duration;
+
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
 
 use crate::{
     entities::{
-        enums::{TestStatus, ManifestKind},
-        graph::{TestExecutable, TestNode}, conversions::new_uuidv4,
+        conversions::new_uuidv4,
+        enums::{HookKind, ManifestKind, TestStatus},
+        graph::{TestExecutable, TestNode},
+        validation::Location,
     },
     traits::Manifest,
 };
 
+use super::{daemons_as_test_nodes, format_daemons_ids, set_daemons_locations, with_hooks};
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct MScriptFile {
+    /// Runs before all the tests
+    pub setup: Option<Hook>,
+    /// Runs after all the tests, even if they fail
+    pub teardown: Option<Hook>,
     pub services: Vec<Service>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Service {
     pub name: String,
+    /// Runs before the service tests
+    pub setup: Option<Hook>,
+    /// Runs after all the service tests, even if they fail
+    pub teardown: Option<Hook>,
+    /// Background processes used by the tests, they run until the end of the run
+    #[serde(default, alias = "background")]
+    pub daemons: Vec<Daemon>,
     pub tests: Vec<TestUnit>,
+    /// Default timeout for the service tests, e.g: `30s` or `1m 30s`
+    #[serde(default, with = "humantime_serde")]
+    pub timeout: Option<Duration>,
+    /// Default retries for the service tests
+    pub retries: Option<u32>,
+    #[serde(default, with = "humantime_serde")]
+    pub retry_delay: Option<Duration>,
+    pub retry_on: Option<RetryOn>,
+    /// Maximum number of the service tests running at the same time
+    pub max_parallel: Option<usize>,
+    #[serde(skip)]
+    pub location: Location,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -25,28 +54,74 @@ pub struct TestUnit {
     pub name: String,
     #[serde(default = "new_uuidv4")]
     pub id: String,
+    /// The tests ids this test depends on, optionally with a condition
     #[serde(default = "Vec::new")]
-    pub depends_on: Vec<String>,
+    pub depends_on: Vec<Dependency>,
     pub command: String,
     pub description: String,
-    pub expect: Option<ReqSpec>
+    pub expect: Option<ReqSpec>,
+    /// The test timeout, it overrides the service timeout
+    #[serde(default, with = "humantime_serde")]
+    pub timeout: Option<Duration>,
+    /// How many times the test is retried after a failure, it overrides the service retries
+    pub retries: Option<u32>,
+    /// The delay before the first retry, it doubles on each retry
+    #[serde(default, with = "humantime_serde")]
+    pub retry_delay: Option<Duration>,
+    pub retry_on: Option<RetryOn>,
+    /// Named resources (e.g: a database) that can't be used by two tests at the same time
+    #[serde(default, alias = "resources")]
+    pub locks: Vec<String>,
+    /// Runs the test alone, without any other test at the same time
+    #[serde(default)]
+    pub exclusive: bool,
+    /// Labels used to select the tests to run, e.g: `run --tags smoke`
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(skip)]
+    pub location: Location,
 }
 
-#[derive(Debug, Serialize, Deserialize, Eq, PartialEq, Clone)]
-pub struct ReqSpec {
-    pub status: Option<String>,
-    pub output: Option<String>,
-}
+pub use super::{Daemon, Dependency, Hook, ReadyProbe, ReqSpec, RetryOn};
 
 impl MScriptFile {
+    /// All the service names defined in the manifest
+    pub fn service_names(&self) -> Vec<String> {
+        self.services.iter().map(|s| s.name.clone()).collect()
+    }
+
+    /// Sets the services and tests locations, the locate function receives the value path,
+    /// e.g: `services[0].tests[1]`.
+    pub fn set_locations(&mut self, locate: impl Fn(&str) -> Location) {
+        for (hook, path) in [(&mut self.setup, "setup"), (&mut self.teardown, "teardown")] {
+            if let Some(hook) = hook {
+                hook.location = locate(path);
+            }
+        }
+        for (i, service) in self.services.iter_mut().enumerate() {
+            service.location = locate(&format!("services[{}]", i));
+            for (hook, name) in [
+                (&mut service.setup, "setup"),
+                (&mut service.teardown, "teardown"),
+            ] {
+                if let Some(hook) = hook {
+                    hook.location = locate(&format!("services[{}].{}", i, name));
+                }
+            }
+            set_daemons_locations(&mut service.daemons, i, &locate);
+            for (j, test) in service.tests.iter_mut().enumerate() {
+                test.location = locate(&format!("services[{}].tests[{}]", i, j));
+            }
+        }
+    }
+
     /// Format all test `id` and depends_on ids as <service>.<test_id>
-    pub fn format_test_ids(&mut self) {
-        let service_names = self
-            .services
-            .iter()
-            .map(|s| s.name.clone())
-            .collect::<Vec<String>>();
+    ///
+    /// The service names are used to detect if a dependency id already has a service,
+    /// so they must include the services from all manifests in the same workflow.
+    pub fn format_test_ids(&mut self, service_names: &[String]) {
         for service in self.services.iter_mut() {
+            format_daemons_ids(&mut service.daemons, &service.name);
             for test in service.tests.iter_mut() {
                 // Format the test_id as <service>.<test_id>
                 test.id = format!("{}.{}", service.name, test.id);
@@ -54,10 +129,10 @@ impl MScriptFile {
                 // If the first split element is not a service name, it means that the dependency id
                 // is from a test for the same service, so we add the service name to it.
                 test.depends_on.iter_mut().for_each(|dep| {
-                    let mut slice = dep.split('.');
+                    let mut slice = dep.id.split('.');
                     let service_name = slice.nth(0).unwrap_or_default();
                     if !(service_names.contains(&service_name.to_owned())) {
-                        *dep = format!("{}.{}", service.name, dep)
+                        dep.id = format!("{}.{}", service.name, dep.id)
                     }
                 });
             }
@@ -77,19 +152,19 @@ impl MScriptFile {
 
 impl Manifest for MScriptFile {
     fn normalize(&mut self) -> Result<()> {
-        self.format_test_ids();
+        self.format_test_ids(&self.service_names());
         Ok(())
     }
     fn as_test_nodes(&self) -> Result<Vec<TestNode>> {
         let mut nodes: Vec<TestNode> = Vec::new();
-        let mut index: u32 = 0;
         for service in self.services.iter() {
+            let mut tests = daemons_as_test_nodes(&service.daemons, &service.name, service.timeout);
             for test in service.tests.iter() {
-                nodes.push(TestNode {
+                tests.push(TestNode {
                     id: test.id.clone(),
-                    index,
+                    index: 0,
                     status: vec![TestStatus::NotStarted],
-                    depends_on: test.depends_on.clone(),
+                    depends_on: test.depends_on.iter().map(|d| d.id.clone()).collect(),
                     executable: TestExecutable {
                         name: test.name.clone(),
                         service: service.name.clone(),
@@ -99,12 +174,45 @@ impl Manifest for MScriptFile {
                         output: None,
                         exit_code: None,
                         kind: ManifestKind::Scripts,
-                        
+                        grpc: None,
+                        expect: test.expect.clone(),
+                        reason: None,
+                        timeout: test.timeout.or(service.timeout),
+                        retries: test.retries.or(service.retries).unwrap_or_default(),
+                        retry_delay: test.retry_delay.or(service.retry_delay),
+                        retry_on: test.retry_on.clone().or(service.retry_on.clone()),
+                        max_parallel: service.max_parallel,
+                        locks: test.locks.clone(),
+                        conditions: test
+                            .depends_on
+                            .iter()
+                            .map(|d| (d.id.clone(), d.on))
+                            .collect(),
+                        exclusive: test.exclusive,
+                        tags: test.tags.clone(),
+                        hook: None,
+                        daemon: None,
+                        location: test.location.clone(),
                     },
                 });
-                index += 1;
             }
+            let hook_node = |hook: &Option<Hook>, kind: HookKind| {
+                hook.as_ref().map(|hook| {
+                    let mut node = hook.as_test_node(kind, Some(&service.name));
+                    node.executable.timeout = hook.timeout.or(service.timeout);
+                    node
+                })
+            };
+            nodes.append(&mut with_hooks(
+                tests,
+                hook_node(&service.setup, HookKind::Setup),
+                hook_node(&service.teardown, HookKind::Teardown),
+            ));
         }
+        nodes
+            .iter_mut()
+            .enumerate()
+            .for_each(|(index, node)| node.index = index as u32);
         Ok(nodes)
     }
 }
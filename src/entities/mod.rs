@@ -1,6 +1,7 @@
-pub mod manifests;
+pub mod api;
+pub mod conversions;
 pub mod enums;
 pub mod graph;
-pub mod conversions;
+pub mod manifests;
 pub mod storage;
-pub mod api;
+pub mod validation;
@@ -1,6 +1,57 @@
-use serde::{Serialize, Deserialize};
+use std::fmt::Display;
 
-#[derive(Clone)]
+use serde::{Deserialize, Serialize};
+
+/// The errors of any storage backend.
+///
+/// It is used as an error, so it can be retrieved from an `anyhow::Error` with `downcast_ref`.
+#[derive(Debug)]
+pub enum StorageError {
+    /// The database failed, e.g: it is locked or its schema is unexpected
+    Database(String),
+    /// The storage files can't be read or written
+    Io(std::io::Error),
+    /// A stored record can't be serialized or deserialized
+    Serialization(String),
+}
+
+pub type StorageResult<T> = Result<T, StorageError>;
+
+impl Display for StorageError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StorageError::Database(message) => {
+                f.write_fmt(format_args!("Storage database error: {}", message))
+            }
+            StorageError::Io(err) => f.write_fmt(format_args!("Storage io error: {}", err)),
+            StorageError::Serialization(message) => {
+                f.write_fmt(format_args!("Storage serialization error: {}", message))
+            }
+        }
+    }
+}
+
+impl std::error::Error for StorageError {}
+
+impl From<rusqlite::Error> for StorageError {
+    fn from(err: rusqlite::Error) -> Self {
+        StorageError::Database(err.to_string())
+    }
+}
+
+impl From<std::io::Error> for StorageError {
+    fn from(err: std::io::Error) -> Self {
+        StorageError::Io(err)
+    }
+}
+
+impl From<serde_json::Error> for StorageError {
+    fn from(err: serde_json::Error) -> Self {
+        StorageError::Serialization(err.to_string())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DbNode {
     pub id: i32,
     pub name: String,
@@ -9,14 +60,69 @@ pub struct DbNode {
     pub service: String,
 }
 
-#[derive(Clone, Default)]
+/// A run of the workflow, with its final counts once finished.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DbRun {
+    pub id: i64,
+    /// The manifest file (or directory) path
+    pub manifest: String,
+    pub started_at: String,
+    pub finished_at: Option<String>,
+    pub completed: u32,
+    pub failed: u32,
+    pub assertion_failed: u32,
+    pub skipped: u32,
+    pub cancelled: u32,
+    pub total: u32,
+}
+
+/// A migration of the database schema, applied or still pending.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SchemaMigration {
+    pub version: u32,
+    pub description: String,
+    /// None while the migration is pending
+    pub applied_at: Option<String>,
+}
+
+/// The schema state of the database.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SchemaInfo {
+    /// The last migration applied, zero if none
+    pub version: u32,
+    /// The last migration known by this version of thorust
+    pub latest: u32,
+    pub migrations: Vec<SchemaMigration>,
+}
+
+impl Display for SchemaInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!(
+            "Schema version: {} (latest: {})",
+            self.version, self.latest
+        ))?;
+        for migration in self.migrations.iter() {
+            let state = match &migration.applied_at {
+                Some(applied_at) => format!("applied at {}", applied_at),
+                None => "pending".to_string(),
+            };
+            f.write_fmt(format_args!(
+                "\n  {} {} ({})",
+                migration.version, migration.description, state
+            ))?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct DbGraph {
     pub id: i32,
     pub dot: String,
     pub created_at: String,
 }
 
-#[derive(Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct NodeHistory {
     pub id: i32,
     pub status: String,
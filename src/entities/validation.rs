@@ -0,0 +1,242 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Display,
+};
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::services::templates::references;
+
+use super::{enums::ManifestKind, graph::TestNode, manifests::ReadyProbe};
+
+/// Where something was declared in a manifest file.
+///
+/// The line and column start at 1, zero means unknown.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Location {
+    pub file: String,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Display for Location {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.line {
+            0 => f.write_str(&self.file),
+            _ => f.write_fmt(format_args!("{}:{}:{}", self.file, self.line, self.column)),
+        }
+    }
+}
+
+/// A single problem found in the manifests.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ValidationProblem {
+    pub location: Location,
+    pub message: String,
+}
+
+impl Display for ValidationProblem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!("{}: {}", self.location, self.message))
+    }
+}
+
+/// All the problems found in the manifests at once.
+///
+/// It is used as an error, so it can be retrieved from an `anyhow::Error` with `downcast_ref`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ValidationErrors {
+    pub problems: Vec<ValidationProblem>,
+}
+
+impl ValidationErrors {
+    pub fn push(&mut self, location: &Location, message: String) {
+        self.problems.push(ValidationProblem {
+            location: location.clone(),
+            message,
+        });
+    }
+
+    /// Ok if there is no problem, the errors themselves otherwise.
+    pub fn into_result(self) -> Result<(), Self> {
+        match self.problems.is_empty() {
+            true => Ok(()),
+            false => Err(self),
+        }
+    }
+}
+
+impl Display for ValidationErrors {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!(
+            "Found {} problem(s) in the manifests:",
+            self.problems.len()
+        ))?;
+        for problem in self.problems.iter() {
+            f.write_fmt(format_args!("\n  {}", problem))?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ValidationErrors {}
+
+/// Checks if the service names are unique across all manifests.
+pub fn validate_services(services: &[(String, Location)], errors: &mut ValidationErrors) {
+    let mut seen: HashMap<&str, &Location> = HashMap::new();
+    for (name, location) in services.iter() {
+        match seen.get(name.as_str()) {
+            Some(first) => errors.push(
+                location,
+                format!(
+                    "The service name '{}' is duplicated, first declared at {}",
+                    name, first
+                ),
+            ),
+            None => {
+                seen.insert(name, location);
+            }
+        }
+    }
+}
+
+/// Checks the tests integrity, collecting all problems:
+/// * duplicated test ids
+/// * depends_on ids that doesn't exist, refers to the test itself or are repeated
+/// * empty commands (or gRPC methods and protos)
+/// * invalid retry_on output regexes
+/// * invalid daemons probes: log regexes or non http:// urls
+/// * templates referring to tests that are not ancestors of the test
+pub fn validate_test_nodes(nodes: &[TestNode], errors: &mut ValidationErrors) {
+    let mut nodes_by_id: HashMap<&str, &TestNode> = HashMap::new();
+    for node in nodes.iter() {
+        match nodes_by_id.get(node.id.as_str()) {
+            Some(first) => errors.push(
+                &node.executable.location,
+                format!(
+                    "The test id '{}' is duplicated, first declared at {}",
+                    node.id, first.executable.location
+                ),
+            ),
+            None => {
+                nodes_by_id.insert(&node.id, node);
+            }
+        }
+    }
+    for node in nodes.iter() {
+        let location = &node.executable.location;
+        let mut seen_deps = HashSet::new();
+        for dep in node.depends_on.iter() {
+            if !seen_deps.insert(dep) {
+                errors.push(
+                    location,
+                    format!(
+                        "The test id '{}' depends on '{}' more than once, a dependency has a single condition",
+                        node.id, dep
+                    ),
+                );
+            } else if *dep == node.id {
+                errors.push(
+                    location,
+                    format!("The test id '{}' depends on itself", node.id),
+                );
+            } else if !nodes_by_id.contains_key(dep.as_str()) {
+                errors.push(
+                    location,
+                    format!(
+                        "The test id '{}' depends on '{}', which does not exist",
+                        node.id, dep
+                    ),
+                );
+            }
+        }
+        let empty_fields = match (&node.executable.kind, &node.executable.grpc) {
+            (ManifestKind::Grpc, Some(grpc)) => [("method", &grpc.method), ("proto", &grpc.proto)]
+                .into_iter()
+                .filter(|(_, v)| v.trim().is_empty())
+                .map(|(f, _)| f)
+                .collect(),
+            _ => match node.executable.command.trim().is_empty() {
+                true => vec!["command"],
+                false => vec![],
+            },
+        };
+        for field in empty_fields {
+            errors.push(
+                location,
+                format!("The test id '{}' has an empty {}", node.id, field),
+            );
+        }
+        if let Some(pattern) = node
+            .executable
+            .retry_on
+            .as_ref()
+            .and_then(|r| r.output.as_ref())
+        {
+            if let Err(err) = Regex::new(pattern) {
+                errors.push(
+                    location,
+                    format!(
+                        "The test id '{}' has an invalid retry_on output regex: {}",
+                        node.id, err
+                    ),
+                );
+            }
+        }
+        match &node.executable.daemon {
+            Some(ReadyProbe::Log(pattern)) => {
+                if let Err(err) = Regex::new(pattern) {
+                    errors.push(
+                        location,
+                        format!(
+                            "The daemon '{}' has an invalid ready log regex: {}",
+                            node.id, err
+                        ),
+                    );
+                }
+            }
+            Some(ReadyProbe::Http(url)) if !url.starts_with("http://") => errors.push(
+                location,
+                format!(
+                    "The daemon '{}' has an invalid ready url '{}', only http:// is supported",
+                    node.id, url
+                ),
+            ),
+            _ => (),
+        }
+
+        let refs = node
+            .executable
+            .templates()
+            .into_iter()
+            .flat_map(|t| references(t))
+            .collect::<Vec<_>>();
+        if refs.is_empty() {
+            continue;
+        }
+        // walk over the depends_on clauses to find all the test ancestors,
+        // otherwise the referenced result could not exist when the test runs.
+        let mut ancestors: HashSet<&str> = HashSet::new();
+        let mut stack: Vec<&str> = node.depends_on.iter().map(|d| d.as_str()).collect();
+        while let Some(id) = stack.pop() {
+            if ancestors.insert(id) {
+                if let Some(dep) = nodes_by_id.get(id) {
+                    stack.extend(dep.depends_on.iter().map(|d| d.as_str()));
+                }
+            }
+        }
+        let mut reported = HashSet::new();
+        for r in refs.iter() {
+            if !ancestors.contains(r.test_id.as_str()) && reported.insert(r) {
+                errors.push(
+                    location,
+                    format!(
+                        "The test id '{}' refers to '{}.{}', but '{}' is not one of its dependencies",
+                        node.id, r.test_id, r.field, r.test_id
+                    ),
+                );
+            }
+        }
+    }
+}
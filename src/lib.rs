@@ -1,9 +1,10 @@
+pub mod api;
+pub mod db;
 pub mod entities;
+pub mod lint;
+pub mod logs;
 pub mod parser;
-pub mod workflow;
 pub mod runner;
-pub mod traits;
-pub mod logs;
-pub mod api;
-pub mod db;
 pub mod services;
+pub mod traits;
+pub mod workflow;
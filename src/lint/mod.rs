@@ -0,0 +1,178 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Display,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    entities::{
+        graph::TestNode,
+        manifests::BaseManifest,
+        validation::{Location, ValidationErrors, ValidationProblem},
+    },
+    parser::parse,
+    traits::{GraphWorkflow, Manifest},
+    workflow::Workflow,
+};
+
+/// The result of checking the manifests without running them.
+///
+/// Errors make the manifests unusable, warnings are only suggestions.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct LintReport {
+    pub errors: Vec<ValidationProblem>,
+    pub warnings: Vec<ValidationProblem>,
+}
+
+impl LintReport {
+    pub fn is_ok(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    fn error(&mut self, location: Location, message: String) {
+        self.errors.push(ValidationProblem { location, message });
+    }
+
+    fn warning(&mut self, location: &Location, message: String) {
+        self.warnings.push(ValidationProblem {
+            location: location.clone(),
+            message,
+        });
+    }
+}
+
+impl Display for LintReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for error in self.errors.iter() {
+            f.write_fmt(format_args!("error: {}\n", error))?;
+        }
+        for warning in self.warnings.iter() {
+            f.write_fmt(format_args!("warning: {}\n", warning))?;
+        }
+        f.write_fmt(format_args!(
+            "{} error(s), {} warning(s)",
+            self.errors.len(),
+            self.warnings.len()
+        ))
+    }
+}
+
+/// Parses and checks the manifest file (or directory), without running any test.
+///
+/// The manifests are normalized (which validates them) and the workflow is checked for cycles,
+/// only after that the lint warnings are collected.
+pub fn lint(path: &str) -> LintReport {
+    let mut report = LintReport::default();
+    let unknown = Location {
+        file: path.to_string(),
+        ..Default::default()
+    };
+    let manifest = match parse(path) {
+        Ok(manifest) => manifest,
+        Err(err) => {
+            match err.downcast::<ValidationErrors>() {
+                Ok(errors) => report.errors.extend(errors.problems),
+                Err(err) => report.error(unknown, err.to_string()),
+            }
+            return report;
+        }
+    };
+    let workflow = match Workflow::new(manifest.clone()) {
+        Ok(workflow) => workflow,
+        Err(err) => {
+            report.error(unknown, err.to_string());
+            return report;
+        }
+    };
+    if let Err(err) = workflow.is_cyclic() {
+        report.error(unknown, err.to_string());
+        return report;
+    }
+    match manifest.as_test_nodes() {
+        Ok(nodes) => {
+            lint_services(&manifest, &mut report);
+            lint_test_nodes(&nodes, &mut report);
+        }
+        Err(err) => report.error(unknown, err.to_string()),
+    }
+    report
+}
+
+/// Warns about services without tests, they are not used by the workflow.
+fn lint_services(manifest: &BaseManifest, report: &mut LintReport) {
+    let mut services = vec![];
+    if let Some(scripts) = &manifest.scripts {
+        services.extend(
+            scripts
+                .services
+                .iter()
+                .map(|s| (&s.name, &s.location, s.tests.is_empty())),
+        );
+    }
+    if let Some(grpc) = &manifest.grpc {
+        services.extend(
+            grpc.services
+                .iter()
+                .map(|s| (&s.name, &s.location, s.tests.is_empty())),
+        );
+    }
+    for (name, location, unused) in services {
+        if unused {
+            report.warning(location, format!("The service '{}' has no tests", name));
+        }
+    }
+}
+
+/// Warns about tests with no description and redundant dependencies, the hooks are left out.
+///
+/// A dependency is redundant when it is already an ancestor of another dependency,
+/// e.g: if `c` depends on `a` and `b`, and `b` depends on `a`, then `a` is redundant for `c`.
+fn lint_test_nodes(nodes: &[TestNode], report: &mut LintReport) {
+    let nodes_by_id: HashMap<&str, &TestNode> = nodes.iter().map(|n| (n.id.as_str(), n)).collect();
+    let ancestors = |id: &str| {
+        let mut ancestors: HashSet<&str> = HashSet::new();
+        let mut stack: Vec<&str> = nodes_by_id
+            .get(id)
+            .map(|n| n.depends_on.iter().map(|d| d.as_str()).collect())
+            .unwrap_or_default();
+        while let Some(id) = stack.pop() {
+            if ancestors.insert(id) {
+                if let Some(dep) = nodes_by_id.get(id) {
+                    stack.extend(dep.depends_on.iter().map(|d| d.as_str()));
+                }
+            }
+        }
+        ancestors
+    };
+    // The hooks dependencies are added on every test, they are never redundant.
+    let is_hook = |id: &str| {
+        nodes_by_id
+            .get(id)
+            .is_some_and(|n| n.executable.hook.is_some())
+    };
+    for node in nodes.iter().filter(|n| !is_hook(&n.id)) {
+        let location = &node.executable.location;
+        if node.executable.description.trim().is_empty() {
+            report.warning(
+                location,
+                format!("The test id '{}' has no description", node.id),
+            );
+        }
+        for dep in node.depends_on.iter().filter(|d| !is_hook(d)) {
+            let through = node
+                .depends_on
+                .iter()
+                .find(|other| *other != dep && ancestors(other).contains(dep.as_str()));
+            if let Some(through) = through {
+                report.warning(
+                    location,
+                    format!(
+                        "The test id '{}' has a redundant dependency on '{}', it is already required by '{}'",
+                        node.id, dep, through
+                    ),
+                );
+            }
+        }
+    }
+}
@@ -43,6 +43,24 @@ pub fn log_change_status(node: &TestNode, status: &TestStatus, _new_line: bool)
             node.executable.name.bold(),
             "AssertionFailed".bold().purple(),
         )),
+        TestStatus::Retrying => Some(format!(
+            "{} - {} {}! {}",
+            node.executable.service.bold().yellow(),
+            node.executable.name.bold(),
+            "Retrying".bold().yellow(),
+            node.executable
+                .reason
+                .clone()
+                .or(node.executable.output.clone())
+                .unwrap_or_default()
+                .trim(),
+        )),
+        TestStatus::Cancelled => Some(format!(
+            "{} - {} {}!",
+            node.executable.service.bold().magenta(),
+            node.executable.name.bold(),
+            "Cancelled".bold().magenta(),
+        )),
         TestStatus::Skipped => Some(format!(
             "{} - {} {}!",
             node.executable.service.bold().cyan(),
@@ -62,13 +80,27 @@ pub fn log_report(workflow: RwLockReadGuard<Workflow>, duration: Duration) {
         .node_count();
     let skipped = workflow.filter_graph(FilterOptions::skipped()).node_count();
     let failed = workflow.filter_graph(FilterOptions::failed()).node_count();
+    let assertion_failed = workflow
+        .filter_graph(FilterOptions::assertion_failed())
+        .node_count();
+    let cancelled = workflow
+        .filter_graph(FilterOptions::cancelled())
+        .node_count();
+    let flaky = workflow
+        .graph
+        .node_weights()
+        .filter(|node| node.is_flaky())
+        .count();
     let total = workflow.graph.node_count();
 
     let log_text = format!(
-        "Completed: {} ✅ | Skipped: {} ✈️ | Failed: {} ❌ | Total: {} | Duration: {}",
+        "Completed: {} ✅ | Skipped: {} ✈️ | Failed: {} ❌ | Assertion Failed: {} ❗ | Flaky: {} 🔁 | Cancelled: {} 🛑 | Total: {} | Duration: {}",
         completed.to_string().green(),
         skipped.to_string().cyan(),
         failed.to_string().red(),
+        assertion_failed.to_string().purple(),
+        flaky.to_string().yellow(),
+        cancelled.to_string().magenta(),
         total.to_string().bold(),
         format!("{:?}", duration).bold()
     );
@@ -0,0 +1,185 @@
+use std::{collections::HashMap, path::Path};
+
+use anyhow::{anyhow, Result};
+use regex::{Captures, Regex};
+use serde_json::Value;
+
+/// `${VAR}`, `${VAR:-default}` or the escaped form `$${VAR}`, kept as a literal `${VAR}`.
+const ENV_PATTERN: &str =
+    r"\$(?P<escape>\$?)\{(?P<name>[A-Za-z_][A-Za-z0-9_]*)(?::-(?P<default>[^}]*))?\}";
+
+/// Variables used to expand the manifest values.
+///
+/// The process environment takes precedence over the `.env` file next to the manifest.
+pub struct EnvVars {
+    dotenv: HashMap<String, String>,
+}
+
+impl EnvVars {
+    /// Loads the optional `.env` file placed in the same directory of the manifest.
+    pub fn for_manifest(fp: &str) -> Result<Self> {
+        let dotenv_path = Path::new(fp)
+            .parent()
+            .unwrap_or(Path::new("."))
+            .join(".env");
+        let mut dotenv = HashMap::new();
+        if dotenv_path.is_file() {
+            for item in dotenvy::from_path_iter(&dotenv_path)? {
+                let (key, value) = item?;
+                dotenv.insert(key, value);
+            }
+        }
+        Ok(Self { dotenv })
+    }
+
+    pub fn from_map(dotenv: HashMap<String, String>) -> Self {
+        Self { dotenv }
+    }
+
+    pub fn get(&self, name: &str) -> Option<String> {
+        std::env::var(name)
+            .ok()
+            .or_else(|| self.dotenv.get(name).cloned())
+    }
+
+    /// Expands all `${VAR}` and `${VAR:-default}` occurrences in the text.
+    ///
+    /// Returns the name of the first missing variable (without default) as error.
+    pub fn expand(&self, text: &str) -> Result<String, String> {
+        self.expand_with(text, false)
+    }
+
+    /// Like [`EnvVars::expand`], but the missing variables (without default) are kept untouched,
+    /// e.g: to let the shell expand its own variables in a `command`.
+    pub fn expand_known(&self, text: &str) -> String {
+        self.expand_with(text, true).unwrap_or_default()
+    }
+
+    fn expand_with(&self, text: &str, keep_missing: bool) -> Result<String, String> {
+        let regex = Regex::new(ENV_PATTERN).unwrap();
+        let mut missing = None;
+        let expanded = regex.replace_all(text, |c: &Captures| {
+            let name = &c["name"];
+            if !c["escape"].is_empty() {
+                return c[0][1..].to_string();
+            }
+            match (self.get(name), c.name("default")) {
+                (Some(value), _) => value,
+                (None, Some(default)) => default.as_str().to_string(),
+                (None, None) if keep_missing => c[0].to_string(),
+                (None, None) => {
+                    missing.get_or_insert_with(|| name.to_string());
+                    String::new()
+                }
+            }
+        });
+        match missing {
+            Some(name) => Err(name),
+            None => Ok(expanded.into_owned()),
+        }
+    }
+
+    /// Expands all string values (recursively), the keys are kept as they are.
+    ///
+    /// The field path (e.g: `services[0].address`) and the file are used to build the error message.
+    /// The shell `command` fields only expand the variables that are set, the others are left
+    /// to the shell.
+    pub fn expand_value(&self, value: &mut Value, field: &str, fp: &str) -> Result<()> {
+        match value {
+            Value::String(text) if is_command(field) => *text = self.expand_known(text),
+            Value::String(text) => {
+                *text = self.expand(text).map_err(|name| {
+                    anyhow!(
+                        "Missing environment variable '{}' required by the field '{}' in file '{}'",
+                        name,
+                        field,
+                        fp
+                    )
+                })?;
+            }
+            Value::Array(items) => {
+                for (i, item) in items.iter_mut().enumerate() {
+                    self.expand_value(item, &format!("{}[{}]", field, i), fp)?;
+                }
+            }
+            Value::Object(map) => {
+                for (key, item) in map.iter_mut() {
+                    let field = match field.is_empty() {
+                        true => key.clone(),
+                        false => format!("{}.{}", field, key),
+                    };
+                    self.expand_value(item, &field, fp)?;
+                }
+            }
+            _ => (),
+        }
+        Ok(())
+    }
+}
+
+fn is_command(field: &str) -> bool {
+    field == "command" || field.ends_with(".command")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use serde_json::json;
+
+    use super::EnvVars;
+
+    fn vars() -> EnvVars {
+        EnvVars::from_map(HashMap::from([
+            ("THORUST_TEST_HOST".to_string(), "localhost".to_string()),
+            ("THORUST_TEST_PORT".to_string(), "50051".to_string()),
+        ]))
+    }
+
+    #[test]
+    fn assert_env_expand_with_defaults_and_escapes() {
+        let vars = vars();
+        assert_eq!(
+            vars.expand("${THORUST_TEST_HOST}:${THORUST_TEST_PORT}")
+                .unwrap(),
+            "localhost:50051"
+        );
+        assert_eq!(
+            vars.expand("${THORUST_TEST_UNSET:-127.0.0.1}").unwrap(),
+            "127.0.0.1"
+        );
+        assert_eq!(vars.expand("${THORUST_TEST_UNSET:-}").unwrap(), "");
+        assert_eq!(
+            vars.expand("for i in 1 2; do echo $${i} $i; done").unwrap(),
+            "for i in 1 2; do echo ${i} $i; done"
+        );
+    }
+
+    #[test]
+    fn assert_env_expand_missing_variable_names_file_and_field() {
+        let mut value = json!({
+            "services": [{"name": "foo", "address": "${THORUST_TEST_UNSET}:50051"}]
+        });
+        let err = vars()
+            .expand_value(&mut value, "", "foo.grpc.yaml")
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Missing environment variable 'THORUST_TEST_UNSET' required by the field 'services[0].address' in file 'foo.grpc.yaml'"
+        );
+    }
+
+    #[test]
+    fn assert_env_expand_keeps_the_shell_variables_in_commands() {
+        let mut value = json!({
+            "services": [{"tests": [{"command": "echo ${THORUST_TEST_HOST} ${HOME_DIR:-/} ${i}"}]}]
+        });
+        vars()
+            .expand_value(&mut value, "", "foo.scripts.yaml")
+            .unwrap();
+        assert_eq!(
+            value["services"][0]["tests"][0]["command"],
+            "echo localhost / ${i}"
+        );
+    }
+}
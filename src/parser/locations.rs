@@ -0,0 +1,156 @@
+use std::collections::HashMap;
+
+use yaml_rust::{
+    parser::{Event, MarkedEventReceiver, Parser},
+    scanner::Marker,
+};
+
+use crate::entities::validation::Location;
+
+enum Frame {
+    Mapping { path: String, key: Option<String> },
+    Sequence { path: String, index: usize },
+}
+
+/// Receives the yaml events, tracking the path of each value, e.g: `services[0].tests[1].command`
+#[derive(Default)]
+struct LocationsReceiver {
+    stack: Vec<Frame>,
+    locations: HashMap<String, (usize, usize)>,
+}
+
+impl LocationsReceiver {
+    /// Path for the next value in the current frame.
+    ///
+    /// Returns None if the next event is a mapping key instead of a value.
+    fn next_path(&self) -> Option<String> {
+        match self.stack.last() {
+            None => Some(String::new()),
+            Some(Frame::Mapping { key: None, .. }) => None,
+            Some(Frame::Mapping {
+                path,
+                key: Some(key),
+                ..
+            }) => match path.is_empty() {
+                true => Some(key.clone()),
+                false => Some(format!("{}.{}", path, key)),
+            },
+            Some(Frame::Sequence { path, index }) => Some(format!("{}[{}]", path, index)),
+        }
+    }
+
+    /// Moves the current frame to the next value
+    fn value_consumed(&mut self) {
+        match self.stack.last_mut() {
+            Some(Frame::Mapping { key, .. }) => *key = None,
+            Some(Frame::Sequence { index, .. }) => *index += 1,
+            None => (),
+        }
+    }
+}
+
+impl MarkedEventReceiver for LocationsReceiver {
+    fn on_event(&mut self, ev: Event, mark: Marker) {
+        let is_node = matches!(
+            ev,
+            Event::Scalar(..) | Event::Alias(_) | Event::MappingStart(_) | Event::SequenceStart(_)
+        );
+        if !is_node {
+            if let Event::MappingEnd | Event::SequenceEnd = ev {
+                self.stack.pop();
+                self.value_consumed();
+            }
+            return;
+        }
+        let path = match self.next_path() {
+            Some(path) => path,
+            // a mapping key, the field location is the key location
+            None => {
+                if let (Event::Scalar(key, ..), Some(Frame::Mapping { path, key: current })) =
+                    (&ev, self.stack.last_mut())
+                {
+                    *current = Some(key.clone());
+                    // a block mapping is only marked after its first key is scanned,
+                    // so the mapping location is moved back to the first key.
+                    let key_location = (mark.line(), mark.col() + 1);
+                    if let Some(location) = self.locations.get_mut(path.as_str()) {
+                        *location = key_location.min(*location);
+                    }
+                }
+                if let Some(path) = self.next_path() {
+                    self.locations
+                        .entry(path)
+                        .or_insert((mark.line(), mark.col() + 1));
+                }
+                return;
+            }
+        };
+        self.locations
+            .entry(path.clone())
+            .or_insert((mark.line(), mark.col() + 1));
+        match ev {
+            Event::MappingStart(_) => self.stack.push(Frame::Mapping { path, key: None }),
+            Event::SequenceStart(_) => self.stack.push(Frame::Sequence { path, index: 0 }),
+            _ => self.value_consumed(),
+        }
+    }
+}
+
+/// Index with the location (line and column) of each value in a yaml or json content.
+///
+/// The values are indexed by their path, e.g: `services[0].tests[1].command`.
+pub struct Locations {
+    file: String,
+    locations: HashMap<String, (usize, usize)>,
+}
+
+impl Locations {
+    /// Indexes the file content, an invalid content results in an empty index.
+    pub fn new(file: &str, content: &str) -> Self {
+        let mut receiver = LocationsReceiver::default();
+        let mut parser = Parser::new(content.chars());
+        let _ = parser.load(&mut receiver, false);
+        Self {
+            file: file.to_string(),
+            locations: receiver.locations,
+        }
+    }
+
+    /// The location of the value in the path,
+    /// if the path was not found, only the file is returned.
+    pub fn get(&self, path: &str) -> Location {
+        let (line, column) = self.locations.get(path).cloned().unwrap_or_default();
+        Location {
+            file: self.file.clone(),
+            line,
+            column,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Locations;
+
+    #[test]
+    fn assert_locations_for_yaml_paths() {
+        let content = "type: scripts\nservices:\n  - name: foo\n    tests:\n      - name: test1\n        command: echo 1\n";
+        let locations = Locations::new("foo.yaml", content);
+
+        assert_eq!(locations.get("services[0]").line, 3);
+        assert_eq!(locations.get("services[0].tests[0]").line, 5);
+        let command = locations.get("services[0].tests[0].command");
+        assert_eq!((command.line, command.column), (6, 9));
+        assert_eq!(command.file, "foo.yaml");
+        assert_eq!(locations.get("services[1]").line, 0);
+    }
+
+    #[test]
+    fn assert_locations_for_json_paths() {
+        let content = "{\n  \"services\": [\n    {\n      \"name\": \"foo\",\n      \"tests\": []\n    }\n  ]\n}";
+        let locations = Locations::new("foo.json", content);
+
+        assert_eq!(locations.get("services[0]").line, 3);
+        assert_eq!(locations.get("services[0].tests").line, 5);
+    }
+}
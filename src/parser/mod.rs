@@ -2,18 +2,27 @@ use anyhow::{anyhow, Result};
 use petgraph::prelude::DiGraph;
 use regex::Regex;
 use serde::de;
-use std::{fs::File, path::Path, str::FromStr};
+use serde_json::Value;
+use std::{path::Path, str::FromStr};
 
 use crate::{
     entities::{
-        enums::{ExtType, ManifestKind},
+        enums::{DependencyCondition, ExtType, HookKind, ManifestKind},
         graph::TestNode,
-        manifests::BaseManifest,
+        manifests::{grpc::MGrpcFile, scripts::MScriptFile, BaseManifest},
+        validation::{Location, ValidationErrors},
     },
     traits::Manifest,
 };
 
-pub fn orphan_nodes<'a>(graph: &DiGraph<&'a TestNode, &'a usize>) -> Vec<&'a TestNode> {
+use self::{env::EnvVars, locations::Locations};
+
+pub mod env;
+pub mod locations;
+
+pub fn orphan_nodes<'a>(
+    graph: &DiGraph<&'a TestNode, &'a DependencyCondition>,
+) -> Vec<&'a TestNode> {
     let mut orphans = Vec::new();
     for node in graph.externals(petgraph::Direction::Incoming) {
         let n = *graph.node_weight(node).unwrap();
@@ -29,22 +38,125 @@ pub struct ParserInfo {
     pub ext: ExtType,
 }
 
-fn serialize_from_ext<T>(path: &str, ext: ExtType) -> Result<T>
+/// Reads the manifest file content, expanding the environment variables in all string values.
+///
+/// Returns the raw content as well, used to locate the problems found later.
+fn read_from_ext(path: &str, ext: &ExtType) -> Result<(String, Value)> {
+    let content = std::fs::read_to_string(path)?;
+    let mut value: Value = parse_content(path, &content, ext)?;
+    EnvVars::for_manifest(path)?.expand_value(&mut value, "", path)?;
+    Ok((content, value))
+}
+
+/// Parses the content as T, the errors carry the file, line and column where they happened.
+fn parse_content<T>(path: &str, content: &str, ext: &ExtType) -> Result<T>
+where
+    T: de::DeserializeOwned,
+{
+    let (location, message) = match ext {
+        ExtType::Json => match serde_json::from_str(content) {
+            Ok(value) => return Ok(value),
+            Err(err) => {
+                let location = Location {
+                    file: path.to_string(),
+                    line: err.line(),
+                    column: err.column(),
+                };
+                (location, err.to_string())
+            }
+        },
+        ExtType::Yaml => match serde_yaml::from_str(content) {
+            Ok(value) => return Ok(value),
+            Err(err) => {
+                let (line, column) = err
+                    .location()
+                    .map(|l| (l.line(), l.column()))
+                    .unwrap_or_default();
+                let location = Location {
+                    file: path.to_string(),
+                    line,
+                    column,
+                };
+                (location, err.to_string())
+            }
+        },
+    };
+    let mut errors = ValidationErrors::default();
+    errors.push(&location, message);
+    Err(errors.into_result().unwrap_err().into())
+}
+
+fn serialize_from_value<T>(value: Value) -> Result<T>
+where
+    T: de::DeserializeOwned,
+{
+    Ok(serde_json::from_value(value)?)
+}
+
+/// Deserializes the manifest with the expanded values.
+///
+/// When it fails, the raw content is parsed again to find where the problem is,
+/// since the expanded value has no locations.
+fn serialize_manifest<T>(path: &str, content: &str, ext: &ExtType, value: Value) -> Result<T>
 where
     T: de::DeserializeOwned,
 {
-    match ext {
-        ExtType::Json => Ok(serde_json::from_reader(File::open(path)?)?),
-        ExtType::Yaml => Ok(serde_yaml::from_reader(File::open(path)?)?),
+    serialize_from_value(value).map_err(|err| match parse_content::<T>(path, content, ext) {
+        Ok(_) => err,
+        Err(located) => located,
+    })
+}
+
+/// The manifest kind declared by the `type` key in the file, if any.
+fn declared_kind(fp: &str, value: &Value) -> Result<Option<ManifestKind>> {
+    match value.get("type") {
+        Some(Value::String(kind)) => ManifestKind::from_str(kind)
+            .map(Some)
+            .map_err(|_| anyhow!("Unknown manifest type '{}' in file {}.", kind, fp)),
+        Some(kind) => Err(anyhow!("Invalid manifest type '{}' in file {}.", kind, fp)),
+        None => Ok(None),
     }
 }
 
-/// Parse manifest file based on ParserInfo (extension, type, etc) extracted from file path.
+fn ext_from_path(fp: &str) -> Result<ExtType> {
+    let ext = Path::new(fp)
+        .extension()
+        .and_then(|e| e.to_str())
+        .ok_or_else(|| anyhow!("Could not find the extension for path {}", fp))?;
+    Ok(ExtType::from_str(ext)?)
+}
+
+/// Checks if the path looks like a manifest: a visible file with a supported extension.
+fn is_manifest_path(path: &Path) -> bool {
+    let hidden = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .map(|name| name.starts_with('.'))
+        .unwrap_or(true);
+    path.is_file() && !hidden && ext_from_path(path.to_str().unwrap_or_default()).is_ok()
+}
+
+/// Parse manifest file based on ParserInfo (extension, type, etc).
+///
+/// The manifest kind is given by the `type` key in the file,
+/// the file path pattern (`<name>.<type>.<ext>`) is used as fallback.
 pub fn parse_file(fp: &str, normalize: bool) -> Result<BaseManifest> {
-    let parser_info = ParserInfo::new(fp)?;
+    let ext = ext_from_path(fp)?;
+    let (content, value) = read_from_ext(fp, &ext)?;
+    let parser_info = ParserInfo::with_declared_kind(fp, declared_kind(fp, &value)?)?;
+    let locations = Locations::new(fp, &content);
+    let locate = |path: &str| locations.get(path);
     let (scripts, grpc) = match parser_info.parser {
-        ManifestKind::Scripts => (serialize_from_ext(fp, parser_info.ext)?, None),
-        ManifestKind::Grpc => (None, serialize_from_ext(fp, parser_info.ext)?),
+        ManifestKind::Scripts => {
+            let mut manifest: MScriptFile = serialize_manifest(fp, &content, &ext, value)?;
+            manifest.set_locations(locate);
+            (Some(manifest), None)
+        }
+        ManifestKind::Grpc => {
+            let mut manifest: MGrpcFile = serialize_manifest(fp, &content, &ext, value)?;
+            manifest.set_locations(locate);
+            (None, Some(manifest))
+        }
     };
     let mut root = BaseManifest { scripts, grpc };
     if normalize {
@@ -57,16 +169,35 @@ pub fn parse_file(fp: &str, normalize: bool) -> Result<BaseManifest> {
 ///
 /// Append all manifests into the dir on the same BaseManifest object.
 pub fn parse_dir(dir: &str, normalize: bool) -> Result<BaseManifest> {
+    // read_dir has no order, sorted so the merge (and the node ids) is the same on every run
+    let mut paths = std::fs::read_dir(dir)?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<std::io::Result<Vec<_>>>()?;
+    paths.sort();
     let mut root_files = vec![];
-    for entry in std::fs::read_dir(dir)? {
-        let entry = entry?;
-        let path = entry.path();
-        if path.is_file() {
+    for path in paths {
+        if is_manifest_path(&path) {
             let file = parse_file(path.to_str().unwrap(), normalize)?;
-            root_files.push(file);
+            root_files.push((path, file));
         }
     }
-    let root = root_files.into_iter().sum();
+    // The run hooks are merged into a single setup and teardown,
+    // so they can only be declared once across all files.
+    for kind in [HookKind::Setup, HookKind::Teardown] {
+        let declared = root_files
+            .iter()
+            .filter(|(_, file)| file.root_hooks().iter().any(|(k, _)| *k == kind))
+            .map(|(path, _)| path.display().to_string())
+            .collect::<Vec<String>>();
+        if declared.len() > 1 {
+            return Err(anyhow!(
+                "The run {} is declared in more than one manifest: {}",
+                kind,
+                declared.join(", ")
+            ));
+        }
+    }
+    let root = root_files.into_iter().map(|(_, file)| file).sum();
     Ok(root)
 }
 
@@ -92,7 +223,7 @@ pub fn parse(fp: &str) -> Result<BaseManifest> {
         true => parse_dir(fp, false),
         false => parse_file(fp, false),
     }?;
-    let _ = root.normalize();
+    root.normalize()?;
     Ok(root)
 }
 
@@ -145,6 +276,62 @@ impl ParserInfo {
             None => Err(anyhow!("No match found for file {}.", fp)),
         }
     }
+
+    /// Create a ParserInfo based on the manifest declared kind (the `type` key) and the file path
+    ///
+    /// The declared kind takes precedence, so plain names like `smoke.yaml` are allowed.
+    /// The file path kind (`<name>.<type>.<ext>`) is only used when the manifest doesn't declare one.
+    ///
+    /// # Remarks
+    ///
+    /// * If the declared kind conflicts with the file path kind, it will return an Error
+    /// * If no kind can be found, it will return an Error
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use thorust::parser::ParserInfo;
+    /// use thorust::entities::enums::{ManifestKind, ExtType};
+    ///
+    /// let parser_info = ParserInfo::with_declared_kind("foo/smoke.yaml", Some(ManifestKind::Grpc)).unwrap();
+    /// assert_eq!(parser_info.filename, "smoke");
+    /// assert_eq!(parser_info.parser, ManifestKind::Grpc);
+    /// assert_eq!(parser_info.ext, ExtType::Yaml);
+    /// ```
+    pub fn with_declared_kind(fp: &str, declared: Option<ManifestKind>) -> Result<ParserInfo> {
+        let ext = ext_from_path(fp)?;
+        let stem = Path::new(fp)
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .ok_or_else(|| anyhow!("Could not find filename for path {}", fp))?;
+        // The kind in the file path is only considered when it is a known one,
+        // otherwise it is just part of the file name, e.g: `smoke.v2.yaml`
+        let path_kind = stem
+            .rsplit_once('.')
+            .and_then(|(name, kind)| ManifestKind::from_str(kind).ok().map(|k| (name, k)));
+        match (declared, path_kind) {
+            (Some(declared), Some((_, path_kind))) if declared != path_kind => Err(anyhow!(
+                "The file {} declares the type '{}' but its name says '{}'.",
+                fp,
+                declared,
+                path_kind
+            )),
+            (_, Some((name, kind))) => Ok(ParserInfo {
+                filename: name.to_owned(),
+                parser: kind,
+                ext,
+            }),
+            (Some(kind), None) => Ok(ParserInfo {
+                filename: stem.to_owned(),
+                parser: kind,
+                ext,
+            }),
+            (None, None) => Err(anyhow!(
+                "Could not find the manifest type for file {}, declare it with `type: <scripts|grpc>` or name the file as <name>.<type>.<ext>.",
+                fp
+            )),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -153,7 +340,7 @@ mod tests {
 
     use crate::{
         entities::enums::{ExtType, ManifestKind},
-        parser::ParserInfo,
+        parser::{parse, ParserInfo},
     };
 
     #[test]
@@ -185,4 +372,52 @@ mod tests {
         assert!(ParserInfo::new("foo/bar.wrong_type.yaml").is_err());
         assert!(ParserInfo::new("foo/bar.scripts.wrong_extension").is_err());
     }
+
+    #[test]
+    fn assert_parser_info_declared_kind_allows_plain_names() {
+        let parser_info =
+            ParserInfo::with_declared_kind("foo/smoke.yaml", Some(ManifestKind::Scripts)).unwrap();
+        assert_eq!(parser_info.filename, "smoke".to_string());
+        assert_eq!(parser_info.parser, ManifestKind::Scripts);
+        assert_eq!(parser_info.ext, ExtType::Yaml);
+
+        let parser_info =
+            ParserInfo::with_declared_kind("foo/smoke.v2.json", Some(ManifestKind::Grpc)).unwrap();
+        assert_eq!(parser_info.filename, "smoke.v2".to_string());
+        assert_eq!(parser_info.parser, ManifestKind::Grpc);
+        assert_eq!(parser_info.ext, ExtType::Json);
+    }
+
+    #[test]
+    fn assert_parser_info_filename_kind_is_a_fallback() {
+        let parser_info = ParserInfo::with_declared_kind("foo/bar.grpc.yaml", None).unwrap();
+        assert_eq!(parser_info.filename, "bar".to_string());
+        assert_eq!(parser_info.parser, ManifestKind::Grpc);
+
+        let parser_info =
+            ParserInfo::with_declared_kind("foo/bar.grpc.yaml", Some(ManifestKind::Grpc)).unwrap();
+        assert_eq!(parser_info.parser, ManifestKind::Grpc);
+    }
+
+    #[test]
+    fn assert_parser_info_declared_kind_errors() {
+        // conflicting kinds
+        assert!(
+            ParserInfo::with_declared_kind("foo/bar.scripts.yaml", Some(ManifestKind::Grpc))
+                .is_err()
+        );
+        // no kind at all
+        assert!(ParserInfo::with_declared_kind("foo/smoke.yaml", None).is_err());
+        // unsupported extension
+        assert!(
+            ParserInfo::with_declared_kind("foo/smoke.toml", Some(ManifestKind::Scripts)).is_err()
+        );
+    }
+
+    #[test]
+    fn assert_parse_file_with_plain_name_uses_declared_type() {
+        let manifest = parse("example.yaml").unwrap();
+        assert!(manifest.scripts.is_some());
+        assert!(manifest.grpc.is_none());
+    }
 }
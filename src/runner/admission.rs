@@ -0,0 +1,29 @@
+use crate::entities::graph::TestExecutable;
+
+/// Checks if the test can start alongside the running tests, respecting:
+/// * the global jobs limit
+/// * the service `max_parallel`
+/// * the named locks, two tests holding the same lock never run at the same time
+/// * exclusive tests, they always run alone
+///
+/// A test can always start when nothing is running, so a limit never blocks the run.
+pub fn can_start(test: &TestExecutable, running: &[&TestExecutable], jobs: Option<usize>) -> bool {
+    if running.is_empty() {
+        return true;
+    }
+    if test.exclusive || running.iter().any(|r| r.exclusive) {
+        return false;
+    }
+    if jobs.is_some_and(|jobs| running.len() >= jobs) {
+        return false;
+    }
+    if let Some(max_parallel) = test.max_parallel {
+        let same_service = running.iter().filter(|r| r.service == test.service).count();
+        if same_service >= max_parallel {
+            return false;
+        }
+    }
+    !running
+        .iter()
+        .any(|r| r.locks.iter().any(|lock| test.locks.contains(lock)))
+}
@@ -0,0 +1,27 @@
+use std::sync::{Arc, Mutex};
+
+use tokio_util::sync::CancellationToken;
+
+/// Cancels the current run, it can be shared with the API and the signal handlers.
+///
+/// A cancel applies to the current run, or to the next one if none is running.
+/// A cancelled token can't be reused, so it is `reset` once the run has ended.
+#[derive(Debug, Clone, Default)]
+pub struct Canceller(Arc<Mutex<CancellationToken>>);
+
+impl Canceller {
+    /// Cancels the current run, the running tests are killed and the pending ones are cancelled.
+    pub fn cancel(&self) {
+        self.0.lock().unwrap().cancel();
+    }
+
+    /// The token of the current run.
+    pub fn token(&self) -> CancellationToken {
+        self.0.lock().unwrap().clone()
+    }
+
+    /// Gives a new token to the next run, once the current one has ended.
+    pub fn reset(&self) {
+        *self.0.lock().unwrap() = CancellationToken::new();
+    }
+}
@@ -1,76 +1,544 @@
-use std::sync::Arc;
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
 
 use crate::{
-    db::SqliteStorage,
-    entities::{enums::TestStatus, graph::TestNode},
+    entities::{
+        enums::{HookKind, TestStatus},
+        graph::{FilterOptions, TestExecutable, TestNode},
+        storage::{DbRun, StorageError, StorageResult},
+    },
     logs::{log_change_status, log_report},
+    services::{
+        assertions::final_status,
+        daemons::Daemons,
+        retries::{retry_delay, should_retry},
+    },
     traits::{GraphWorkflow, RunnerWorkflow, Storage},
     workflow::Workflow,
 };
 use anyhow::Result;
-use tokio::sync::RwLock;
+use petgraph::stable_graph::NodeIndex;
+use tokio::{sync::RwLock, task::JoinSet};
+use tokio_util::sync::CancellationToken;
+
+use self::{admission::can_start, cancel::Canceller};
+
+pub mod admission;
+pub mod cancel;
+
+/// Options for the whole run, applied over all tests.
+#[derive(Debug, Clone, Default)]
+pub struct RunnerOptions {
+    /// Maximum duration of `run_until_complete`,
+    /// the tests still running after that are killed and fail as timed out.
+    pub timeout: Option<Duration>,
+    /// Maximum number of tests running at the same time.
+    pub jobs: Option<usize>,
+    /// Stops starting new tests after the first Failed or AssertionFailed test.
+    pub fail_fast: bool,
+    /// With `fail_fast`, the tests still running are cancelled instead of finishing.
+    pub cancel_running: bool,
+    /// The manifest path, recorded in the run history.
+    pub manifest: Option<String>,
+}
 
 pub struct Runner {
     pub workflow: Arc<RwLock<Workflow>>,
+    pub options: RunnerOptions,
+    /// Cancels the current run, see `Runner::canceller`.
+    canceller: Canceller,
+    /// The daemons started by the current run, killed when it finishes (or the runner is dropped).
+    daemons: Daemons,
+    /// When the current run must finish, given by the run timeout.
+    deadline: Option<Instant>,
+    /// Where the runs, the nodes history and the graphs are kept.
+    storage: Arc<dyn Storage>,
+    /// The id of the current run in the storage, a new one is started on reset.
+    run: i64,
+    /// Whether the end and the final counts of the current run are kept in the storage.
+    finished: bool,
 }
 
 impl Runner {
-    pub fn new(workflow: Workflow) -> Result<Self> {
-        let storage = SqliteStorage::new();
-        storage.insert_test_nodes(workflow.is_cyclic()?);
-        storage.insert_dot(&workflow.as_dot());
+    /// The runner keeps the history of its runs in the given storage.
+    pub fn new(workflow: Workflow, storage: Arc<dyn Storage>) -> Result<Self> {
+        Self::with_options(workflow, RunnerOptions::default(), storage)
+    }
+
+    pub fn with_options(
+        workflow: Workflow,
+        options: RunnerOptions,
+        storage: Arc<dyn Storage>,
+    ) -> Result<Self> {
+        let run = start_run(&*storage, &workflow, &options)?;
         Ok(Self {
             workflow: Arc::new(RwLock::new(workflow)),
+            options,
+            canceller: Canceller::default(),
+            daemons: Daemons::default(),
+            deadline: None,
+            storage,
+            run,
+            finished: false,
         })
     }
+
+    /// The id of the current run in the storage.
+    pub fn run_id(&self) -> i64 {
+        self.run
+    }
+
+    /// The storage where the runner keeps its history.
+    pub fn storage(&self) -> Arc<dyn Storage> {
+        self.storage.clone()
+    }
+
+    /// A handle to cancel the runs from outside, e.g: on Ctrl-C or from the API.
+    pub fn canceller(&self) -> Canceller {
+        self.canceller.clone()
+    }
 }
 
-fn update_db_node_history(node: TestNode, dot: &str) {
-    let storage = SqliteStorage::new();
-    storage.insert_node_history(
+impl Runner {
+    /// Ready-queue scheduler: every time a test finishes, the tests unlocked by it are started,
+    /// as long as the concurrency limits allow them to run (see `admission::can_start`).
+    ///
+    /// If `batch` is present, only these nodes are run.
+    ///
+    /// When the run is cancelled, no more tests are started, the running ones are killed
+    /// and all the pending nodes are marked as Cancelled.
+    /// The teardowns are the exception, they still run once their tests are final,
+    /// and they are neither cancelled nor cut by the run deadline.
+    /// A cancel sent before the run has started applies to it as well.
+    ///
+    /// The same happens on fail-fast, after the first failure,
+    /// but the running tests are only killed if `cancel_running` is set.
+    /// Once the run deadline has passed, no more tests are started either,
+    /// the running ones time out on their own.
+    ///
+    /// If the storage fails, the run is cancelled as well and the storage error is returned,
+    /// since the history can't be kept anymore.
+    async fn schedule(&mut self, batch: Option<HashSet<u32>>) -> Result<()> {
+        let scheduled = self.schedule_with(batch, self.canceller.token()).await;
+        // The cancels sent until now belonged to this run
+        self.canceller.reset();
+        scheduled
+    }
+
+    /// The scheduler loop of `schedule`, until the token is cancelled.
+    async fn schedule_with(
+        &mut self,
+        batch: Option<HashSet<u32>>,
+        cancel: CancellationToken,
+    ) -> Result<()> {
+        let mut tasks = JoinSet::new();
+        // The nodes already spawned are tracked since they stay NotStarted until their task runs.
+        let mut running: HashMap<u32, TestExecutable> = HashMap::new();
+        let mut spawned: HashSet<u32> = HashSet::new();
+        // Why the scheduling has stopped before the end, on fail-fast or after the run deadline
+        let mut stopped: Option<String> = None;
+        let mut storage_error: Option<anyhow::Error> = None;
+        let mut cancelled = Ok(());
+        let mut pending_cancelled = false;
+        // The teardowns always run to the end, even once the run is cancelled
+        let teardowns_cancel = CancellationToken::new();
+        loop {
+            if stopped.is_none() && self.deadline.is_some_and(|d| Instant::now() >= d) {
+                stopped = Some("not started, run timed out".to_string());
+            }
+            let stopping = cancel.is_cancelled() || stopped.is_some();
+            // The pending tests are cancelled right away, so the teardowns waiting for them can run
+            if stopping && !pending_cancelled {
+                pending_cancelled = true;
+                let reason = stopped.as_deref().unwrap_or("cancelled before it started");
+                cancelled = self.cancel_pending(&batch, &spawned, reason, false).await;
+            }
+            let availables = self.workflow.read().await.availables()?;
+            for mut node in availables {
+                let in_batch = batch.as_ref().is_none_or(|b| b.contains(&node.index));
+                let teardown = node.executable.hook == Some(HookKind::Teardown);
+                if !in_batch || spawned.contains(&node.index) || (stopping && !teardown) {
+                    continue;
+                }
+                let executables = running.values().collect::<Vec<_>>();
+                if !can_start(&node.executable, &executables, self.options.jobs) {
+                    // An exclusive test waits for the running ones to finish,
+                    // nothing else starts meanwhile, otherwise it could wait forever.
+                    match node.executable.exclusive {
+                        true => break,
+                        false => continue,
+                    }
+                }
+                spawned.insert(node.index);
+                running.insert(node.index, node.executable.clone());
+                let workflow = self.workflow.clone();
+                let (deadline, cancel) = match teardown {
+                    true => (None, teardowns_cancel.clone()),
+                    false => (self.deadline, cancel.clone()),
+                };
+                let daemons = self.daemons.clone();
+                let history = self.history();
+                tasks.spawn(async move {
+                    let result =
+                        execute_node(&mut node, workflow, history, deadline, cancel, daemons).await;
+                    (node.index, result)
+                });
+            }
+            match tasks.join_next().await {
+                Some(result) => {
+                    let (index, result) = result?;
+                    running.remove(&index);
+                    match result {
+                        Err(err) if err.is::<StorageError>() && storage_error.is_none() => {
+                            storage_error = Some(err);
+                            cancel.cancel();
+                        }
+                        _ => (),
+                    }
+                    if self.options.fail_fast && stopped.is_none() {
+                        stopped = self.fail_fast_reason(index).await;
+                        if stopped.is_some() && self.options.cancel_running {
+                            cancel.cancel();
+                        }
+                    }
+                }
+                None => break,
+            }
+        }
+        // The teardowns that couldn't run, e.g: waiting for tests out of the batch
+        if pending_cancelled {
+            let reason = stopped.as_deref().unwrap_or("cancelled before it started");
+            let teardowns = self.cancel_pending(&batch, &spawned, reason, true).await;
+            cancelled = cancelled.and(teardowns);
+        }
+        match storage_error {
+            Some(err) => Err(err),
+            None => cancelled,
+        }
+    }
+
+    /// Runs the target node and its ancestors in dependency order, the other nodes are left as they are.
+    ///
+    /// With `reuse`, the ancestors that have already completed (e.g: in the current API session)
+    /// are not run again, except the daemons since they don't outlive a run.
+    /// All the other nodes in the subgraph are reset before running.
+    ///
+    /// Returns the target node, with its final status.
+    pub async fn run_target(&mut self, target: &str, reuse: bool) -> Result<TestNode> {
+        let (index, batch) = {
+            let workflow = self.workflow.read().await;
+            let node = workflow
+                .find_node(target)
+                .ok_or_else(|| anyhow::anyhow!("The target '{}' does not exist", target))?;
+            let mut batch: HashSet<u32> = workflow
+                .ancestors(NodeIndex::new(node.index as usize))
+                .into_iter()
+                // the daemons are killed at the end of every run, so they are never reused
+                .filter(|n| {
+                    !(reuse
+                        && n.last_status() == TestStatus::Completed
+                        && n.executable.daemon.is_none())
+                })
+                .map(|n| n.index)
+                .collect();
+            batch.insert(node.index);
+            (node.index, batch)
+        };
+        self.workflow.write().await.reset_nodes(&batch)?;
+        // The daemons of the subgraph are started again, the previous ones are not kept along
+        self.daemons.stop();
+        let start_duration = std::time::Instant::now();
+        self.deadline = self.options.timeout.map(|timeout| start_duration + timeout);
+        let scheduled = self.schedule(Some(batch)).await;
+        self.deadline = None;
+        self.daemons.stop();
+        let workflow = self.workflow.read().await;
+        let node = workflow.graph[NodeIndex::new(index as usize)].clone();
+        self.storage.finish_run(&run_counts(self.run, &workflow))?;
+        self.finished = true;
+        log_report(workflow, start_duration.elapsed());
+        scheduled.map(|_| node)
+    }
+
+    /// The reason to stop the run, if the node has failed.
+    async fn fail_fast_reason(&self, index: u32) -> Option<String> {
+        let workflow = self.workflow.read().await;
+        let node = &workflow.graph[NodeIndex::new(index as usize)];
+        match node.last_status() {
+            TestStatus::Failed | TestStatus::AssertionFailed => Some(format!(
+                "not started, fail-fast after '{}' has {}",
+                node.id,
+                node.last_status()
+            )),
+            _ => None,
+        }
+    }
+
+    /// Marks all the nodes that never started as Cancelled, even if the storage fails meanwhile.
+    ///
+    /// The `spawned` nodes are skipped, they may still be NotStarted until their task runs,
+    /// which then marks them as Running or Cancelled by itself.
+    /// The teardowns are left to run, unless `teardowns` is set.
+    async fn cancel_pending(
+        &self,
+        batch: &Option<HashSet<u32>>,
+        spawned: &HashSet<u32>,
+        reason: &str,
+        teardowns: bool,
+    ) -> Result<()> {
+        let pending = self
+            .workflow
+            .read()
+            .await
+            .filter_graph(FilterOptions::not_started())
+            .node_weights()
+            .filter(|node| batch.as_ref().is_none_or(|b| b.contains(&node.index)))
+            .filter(|node| !spawned.contains(&node.index))
+            .filter(|node| teardowns || node.executable.hook != Some(HookKind::Teardown))
+            .map(|node| (*node).clone())
+            .collect::<Vec<TestNode>>();
+        let mut result = Ok(());
+        for mut node in pending {
+            node.executable.reason = Some(reason.to_string());
+            let pushed = push_status(
+                &mut node,
+                TestStatus::Cancelled,
+                &self.workflow,
+                &self.history(),
+            )
+            .await;
+            result = result.and(pushed);
+        }
+        result
+    }
+
+    /// Ends the run driven test by test or wave by wave (e.g: from the API),
+    /// once there is nothing left to start: the daemons are killed and the run is finished.
+    async fn end_if_exhausted(&mut self) -> Result<()> {
+        if self.workflow.read().await.availables()?.is_empty() {
+            self.daemons.stop();
+            self.finish().await?;
+        }
+        Ok(())
+    }
+
+    /// Keeps the end and the final counts of the current run in the storage.
+    async fn finish(&mut self) -> Result<()> {
+        let counts = run_counts(self.run, &*self.workflow.read().await);
+        self.storage.finish_run(&counts)?;
+        self.finished = true;
+        Ok(())
+    }
+
+    /// Where the nodes history of the current run is written.
+    fn history(&self) -> History {
+        History {
+            storage: self.storage.clone(),
+            run: self.run,
+        }
+    }
+}
+
+/// The storage and the run id, to keep the nodes history.
+#[derive(Clone)]
+struct History {
+    storage: Arc<dyn Storage>,
+    run: i64,
+}
+
+/// Starts a new run in the storage, with all the workflow nodes and its graph.
+fn start_run(storage: &dyn Storage, workflow: &Workflow, options: &RunnerOptions) -> Result<i64> {
+    let run = storage.insert_run(&options.manifest.clone().unwrap_or_default())?;
+    storage.insert_test_nodes(run, workflow.is_cyclic()?)?;
+    storage.insert_dot(run, &workflow.as_dot())?;
+    Ok(run)
+}
+
+/// The final counts of the run, as kept in the run history.
+fn run_counts(run: i64, workflow: &Workflow) -> DbRun {
+    let count = |options: FilterOptions| workflow.filter_graph(options).node_count() as u32;
+    DbRun {
+        id: run,
+        completed: count(FilterOptions::completed()),
+        failed: count(FilterOptions::failed()),
+        assertion_failed: count(FilterOptions::assertion_failed()),
+        skipped: count(FilterOptions::skipped()),
+        cancelled: count(FilterOptions::cancelled()),
+        total: workflow.graph.node_count() as u32,
+        ..Default::default()
+    }
+}
+
+fn update_db_node_history(history: &History, node: &TestNode, dot: &str) -> StorageResult<()> {
+    history.storage.insert_node_history(
+        history.run,
         &node.last_status().to_string(),
         node.index as i64,
-        &node.executable.output.clone().unwrap_or_default(),
-    );
-    storage.insert_dot(dot);
+        &node
+            .executable
+            .reason
+            .clone()
+            .or(node.executable.output.clone())
+            .unwrap_or_default(),
+    )?;
+    history.storage.insert_dot(history.run, dot)?;
+    Ok(())
 }
 
-/// Wrapper that executes a single test node
-async fn execute_node(node: &mut TestNode, workflow: Arc<RwLock<Workflow>>) -> Result<String> {
-    // Set the test status to Running
-    node.status.push(TestStatus::Running);
+/// Pushes a new status into the node, refreshing the workflow state and the node history.
+///
+/// Fails with the first storage error, the workflow state is refreshed anyway.
+async fn push_status(
+    node: &mut TestNode,
+    status: TestStatus,
+    workflow: &Arc<RwLock<Workflow>>,
+    history: &History,
+) -> Result<()> {
+    node.status.push(status);
+    let history = history.clone();
+    let error: Arc<Mutex<Option<StorageError>>> = Arc::default();
+    let first_error = error.clone();
     workflow
         .write()
         .await
         .update_graph_state(node.clone(), move |node, dot| {
-            update_db_node_history(node.clone(), dot);
+            let mut first_error = first_error.lock().unwrap();
+            if first_error.is_none() {
+                *first_error = update_db_node_history(&history, node, dot).err();
+            }
         });
-    log_change_status(&node, &TestStatus::Running, false);
-    match node.executable.call().await {
-        // Set the test status to Completed and update the node history
-        Ok(output) => {
-            node.status.push(TestStatus::Completed);
-            workflow
-                .write()
-                .await
-                .update_graph_state(node.clone(), move |node, dot| {
-                    update_db_node_history(node.clone(), dot);
-                });
-            log_change_status(&node, &TestStatus::Completed, true);
-            Ok(output)
+    log_change_status(node, &status, status != TestStatus::Running);
+    let error = error.lock().unwrap().take();
+    match error {
+        Some(err) => Err(err.into()),
+        None => Ok(()),
+    }
+}
+
+/// Renders the node templates with the results of its ancestors.
+async fn render_templates(node: &mut TestNode, workflow: &Arc<RwLock<Workflow>>) -> Result<()> {
+    let workflow = workflow.read().await;
+    let context: HashMap<String, &TestNode> = workflow
+        .ancestors(NodeIndex::new(node.index as usize))
+        .into_iter()
+        .map(|n| (n.id.clone(), n))
+        .collect();
+    node.executable.render_templates(&context)
+}
+
+/// Marks the running node as Cancelled.
+async fn cancel_node(
+    node: &mut TestNode,
+    workflow: &Arc<RwLock<Workflow>>,
+    history: &History,
+) -> Result<String> {
+    node.executable.exit_code = None;
+    node.executable.reason = Some("cancelled while running".to_string());
+    push_status(node, TestStatus::Cancelled, workflow, history).await?;
+    Err(anyhow::anyhow!("The test '{}' was cancelled", node.id))
+}
+
+/// Pushes a status the test does not end in, marking the test as Failed if it can't be stored.
+///
+/// The graph state is updated even when storing fails, so the test is not left Running.
+async fn push_transient_status(
+    node: &mut TestNode,
+    status: TestStatus,
+    workflow: &Arc<RwLock<Workflow>>,
+    history: &History,
+) -> Result<()> {
+    if let Err(err) = push_status(node, status, workflow, history).await {
+        node.executable.reason = Some(err.to_string());
+        let _ = push_status(node, TestStatus::Failed, workflow, history).await;
+        return Err(err);
+    }
+    Ok(())
+}
+
+/// Calls the test, or starts the daemon.
+async fn call_node(executable: &mut TestExecutable, daemons: &Daemons) -> Result<String> {
+    match executable.daemon {
+        Some(_) => daemons.start(executable).await,
+        None => executable.call().await,
+    }
+}
+
+/// Wrapper that executes a single test node
+///
+/// The test timeout is shortened to the time left until the run deadline, if any,
+/// and there are no more retries once the deadline has passed.
+///
+/// If the run is cancelled meanwhile, the call is dropped (killing its process tree)
+/// and the test is marked as Cancelled.
+///
+/// A daemon node is Completed once it is ready, its process is kept in `daemons`.
+async fn execute_node(
+    node: &mut TestNode,
+    workflow: Arc<RwLock<Workflow>>,
+    history: History,
+    deadline: Option<Instant>,
+    cancel: CancellationToken,
+    daemons: Daemons,
+) -> Result<String> {
+    if cancel.is_cancelled() {
+        node.executable.reason = Some("cancelled before it started".to_string());
+        push_status(node, TestStatus::Cancelled, &workflow, &history).await?;
+        return Err(anyhow::anyhow!("The test '{}' was cancelled", node.id));
+    }
+    // Set the test status to Running
+    push_transient_status(node, TestStatus::Running, &workflow, &history).await?;
+    if let Err(err) = render_templates(node, &workflow).await {
+        node.executable.reason = Some(err.to_string());
+        push_status(node, TestStatus::Failed, &workflow, &history).await?;
+        return Err(err);
+    }
+    let timeout = node.executable.timeout;
+    let time_left = || deadline.map(|d| d.saturating_duration_since(Instant::now()));
+    let mut attempt = 0;
+    let (status, call) = loop {
+        attempt += 1;
+        if let Some(left) = time_left() {
+            node.executable.timeout = Some(timeout.map_or(left, |t| t.min(left)));
         }
-        // Set the test status to Failed and update the node history
-        Err(err) => {
-            node.status.push(TestStatus::Failed);
-            workflow
-                .write()
-                .await
-                .update_graph_state(node.clone(), move |node, dot| {
-                    update_db_node_history(node.clone(), dot);
-                });
-            log_change_status(&node, &TestStatus::Failed, true);
-            Err(err)
+        let call = tokio::select! {
+            biased;
+            _ = cancel.cancelled() => return cancel_node(node, &workflow, &history).await,
+            call = call_node(&mut node.executable, &daemons) => call,
+        };
+        // Set the final test status (Completed, Failed or AssertionFailed)
+        let status = final_status(&mut node.executable, &call);
+        if !should_retry(&node.executable, status, attempt) || time_left() == Some(Duration::ZERO) {
+            break (status, call);
         }
+        // Every failed attempt is kept in the node history before trying again
+        push_transient_status(node, TestStatus::Retrying, &workflow, &history).await?;
+        let delay = retry_delay(&node.executable, attempt);
+        tokio::select! {
+            _ = tokio::time::sleep(time_left().map_or(delay, |left| delay.min(left))) => (),
+            _ = cancel.cancelled() => return cancel_node(node, &workflow, &history).await,
+        };
+        // The run has timed out while waiting, the last attempt is the final one
+        if time_left() == Some(Duration::ZERO) {
+            break (status, call);
+        }
+        node.executable.reason = None;
+        node.executable.output = None;
+        node.executable.exit_code = None;
+        push_transient_status(node, TestStatus::Running, &workflow, &history).await?;
+    };
+    // Update the node history with the final status
+    push_status(node, status, &workflow, &history).await?;
+    match (status, call) {
+        (TestStatus::Completed, _) => Ok(node.executable.output.clone().unwrap_or_default()),
+        (TestStatus::AssertionFailed, _) => Err(anyhow::anyhow!(node
+            .executable
+            .reason
+            .clone()
+            .unwrap_or_default())),
+        (_, call) => call,
     }
 }
 #[async_trait::async_trait]
@@ -78,40 +546,49 @@ impl RunnerWorkflow for Runner {
     async fn execute(&mut self, mut node: TestNode) -> Result<String> {
         // Set the test status to Running
         let workflow = self.workflow.clone();
-        execute_node(&mut node, workflow).await
+        let cancel = self.canceller.token();
+        let daemons = self.daemons.clone();
+        let result = execute_node(
+            &mut node,
+            workflow,
+            self.history(),
+            self.deadline,
+            cancel,
+            daemons,
+        )
+        .await;
+        self.canceller.reset();
+        self.end_if_exhausted().await?;
+        result
     }
     async fn batch_execute(&mut self, nodes: Vec<TestNode>) -> Result<()> {
-        let mut futures = Vec::new();
-        for mut node in nodes {
-            futures.push(tokio::spawn({
-                let workflow = self.workflow.clone();
-                async move { execute_node(&mut node, workflow).await }
-            }));
-        }
-        for future in futures {
-            let _ = future.await?;
-        }
-        Ok(())
+        let batch = nodes.iter().map(|node| node.index).collect();
+        let scheduled = self.schedule(Some(batch)).await;
+        self.end_if_exhausted().await?;
+        scheduled
     }
     async fn run_until_complete(&mut self) -> Result<()> {
         let start_duration = std::time::Instant::now();
-        loop {
-            let availables = self.workflow.read().await.availables()?;
-            if availables.is_empty() {
-                break;
-            }
-            self.batch_execute(availables).await?;
-        }
+        self.deadline = self.options.timeout.map(|timeout| start_duration + timeout);
+        let scheduled = self.schedule(None).await;
+        self.deadline = None;
+        self.daemons.stop();
         let finish_duration = std::time::Instant::now();
-        log_report(self.workflow.read().await, finish_duration - start_duration);
-        Ok(())
+        let workflow = self.workflow.read().await;
+        self.storage.finish_run(&run_counts(self.run, &workflow))?;
+        self.finished = true;
+        log_report(workflow, finish_duration - start_duration);
+        scheduled
     }
     async fn reset(&mut self) -> Result<()> {
-        let _ = std::fs::remove_file("./db");
+        self.daemons.stop();
+        // A run left halfway (e.g: from the API) is finished as it is
+        if !self.finished {
+            self.finish().await?;
+        }
         self.workflow.write().await.reset()?;
-        let storage = SqliteStorage::new();
-        storage.insert_test_nodes(self.workflow.read().await.is_cyclic()?);
-        storage.insert_dot(&self.workflow.read().await.as_dot());
+        self.run = start_run(&*self.storage, &*self.workflow.read().await, &self.options)?;
+        self.finished = false;
         Ok(())
     }
 }
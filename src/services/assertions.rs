@@ -0,0 +1,101 @@
+use anyhow::Result;
+
+use crate::entities::{
+    enums::{ManifestKind, TestStatus},
+    graph::TestExecutable,
+};
+
+/// Applies the `expect` clause over a finished test call and returns the test final status.
+///
+/// Without an `expect` clause, or when the call could not even produce an exit code
+/// (e.g: the gRPC server is unreachable), the call result decides between `Completed` and `Failed`.
+///
+/// An expected status replaces the default success check, so a test can expect a failure.
+///
+/// On a mismatch, the expected vs actual message is stored as the test `reason`.
+pub fn final_status(test: &mut TestExecutable, call: &Result<String>) -> TestStatus {
+    let default_status = match call {
+        Ok(_) => TestStatus::Completed,
+        Err(_) => TestStatus::Failed,
+    };
+    let (expect, exit_code) = match (&test.expect, test.exit_code) {
+        (Some(expect), Some(exit_code)) => (expect.clone(), exit_code),
+        _ => return default_status,
+    };
+    if expect.status.is_none() && call.is_err() {
+        return TestStatus::Failed;
+    }
+
+    let mut mismatches = vec![];
+    if let Some(status) = &expect.status {
+        if !status_matches(&test.kind, status, exit_code) {
+            mismatches.push(format!(
+                "expected status '{}', got '{}'",
+                status,
+                status_name(&test.kind, exit_code)
+            ));
+        }
+    }
+    if let Some(output) = &expect.output {
+        let actual = test.output.clone().unwrap_or_default();
+        if !output_matches(&test.kind, output, &actual) {
+            mismatches.push(format!(
+                "expected output '{}', got '{}'",
+                output.trim(),
+                actual.trim()
+            ));
+        }
+    }
+
+    match mismatches.is_empty() {
+        true => TestStatus::Completed,
+        false => {
+            test.reason = Some(format!(
+                "Assertion failed for '{}': {}",
+                test.id,
+                mismatches.join("; ")
+            ));
+            TestStatus::AssertionFailed
+        }
+    }
+}
+
+/// Scripts statuses are exit codes, gRPC statuses can be a code or its name,
+/// the name is case insensitive and the underscores are ignored: `NotFound`, `NOT_FOUND` and `5` are the same.
+fn status_matches(kind: &ManifestKind, expected: &str, exit_code: i32) -> bool {
+    let expected = expected.trim();
+    if let Ok(code) = expected.parse::<i32>() {
+        return code == exit_code;
+    }
+    match kind {
+        ManifestKind::Grpc => {
+            normalize_status_name(expected)
+                == normalize_status_name(&format!("{:?}", tonic::Code::from_i32(exit_code)))
+        }
+        ManifestKind::Scripts => false,
+    }
+}
+
+fn normalize_status_name(name: &str) -> String {
+    name.replace('_', "").to_lowercase()
+}
+
+fn status_name(kind: &ManifestKind, exit_code: i32) -> String {
+    match kind {
+        ManifestKind::Grpc => format!("{:?} ({})", tonic::Code::from_i32(exit_code), exit_code),
+        ManifestKind::Scripts => exit_code.to_string(),
+    }
+}
+
+/// Outputs are compared ignoring the surrounding whitespaces,
+/// gRPC outputs are compared as JSON values, so the formatting doesn't matter.
+fn output_matches(kind: &ManifestKind, expected: &str, actual: &str) -> bool {
+    if let ManifestKind::Grpc = kind {
+        let expected_json = serde_json::from_str::<serde_json::Value>(expected);
+        let actual_json = serde_json::from_str::<serde_json::Value>(actual);
+        if let (Ok(expected), Ok(actual)) = (expected_json, actual_json) {
+            return expected == actual;
+        }
+    }
+    expected.trim() == actual.trim()
+}
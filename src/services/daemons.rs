@@ -0,0 +1,238 @@
+use std::{
+    os::unix::process::CommandExt,
+    process::Stdio,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use anyhow::Result;
+use regex::Regex;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, AsyncWriteExt, BufReader},
+    net::TcpStream,
+    process::{Child, Command},
+    sync::mpsc::{self, UnboundedReceiver, UnboundedSender},
+};
+
+use crate::entities::{graph::TestExecutable, manifests::ReadyProbe};
+
+use super::test_executable::{timed_out, ProcessGroupGuard};
+
+/// How long to wait for a daemon to be ready, if it has no timeout.
+const DEFAULT_READY_TIMEOUT: Duration = Duration::from_secs(30);
+/// Interval between the TCP and HTTP probes.
+const PROBE_INTERVAL: Duration = Duration::from_millis(100);
+
+/// A daemon that is ready, its whole process tree is killed when dropped.
+pub struct DaemonProcess {
+    _guard: ProcessGroupGuard,
+    _child: Child,
+}
+
+/// The daemons started by the current run, they are stopped when the run finishes.
+#[derive(Default, Clone)]
+pub struct Daemons(Arc<Mutex<Vec<DaemonProcess>>>);
+
+impl Daemons {
+    /// Starts the daemon and waits until it is ready,
+    /// then it keeps running until `stop` is called.
+    pub async fn start(&self, test: &mut TestExecutable) -> Result<String> {
+        let daemon = start_daemon(test).await?;
+        self.0.lock().unwrap().push(daemon);
+        Ok(test.output.clone().unwrap_or_default())
+    }
+
+    /// Kills all the daemons.
+    pub fn stop(&self) {
+        self.0.lock().unwrap().clear();
+    }
+}
+
+/// Sends the lines read into the channel.
+///
+/// It keeps reading after the daemon is ready, otherwise the daemon blocks once the pipe is full.
+fn forward_lines(reader: impl AsyncRead + Unpin + Send + 'static, lines: UnboundedSender<String>) {
+    tokio::spawn(async move {
+        let mut reader = BufReader::new(reader).lines();
+        while let Ok(Some(line)) = reader.next_line().await {
+            let _ = lines.send(line);
+        }
+    });
+}
+
+/// Spawns the daemon in its own process group and waits for its probe.
+///
+/// The lines printed until it is ready are kept as the test output.
+pub async fn start_daemon(test: &mut TestExecutable) -> Result<DaemonProcess> {
+    let probe = test
+        .daemon
+        .clone()
+        .ok_or_else(|| anyhow::anyhow!("The test '{}' is not a daemon", test.name))?;
+    let pattern = match &probe {
+        ReadyProbe::Log(pattern) => Some(Regex::new(pattern)?),
+        _ => None,
+    };
+    let mut command = std::process::Command::new("sh");
+    command.arg("-c").arg(&test.command).process_group(0);
+    let mut child = Command::from(command)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()?;
+    // kills the process group if the daemon is not ready
+    let guard = ProcessGroupGuard(child.id());
+    let (sender, mut lines) = mpsc::unbounded_channel();
+    if let Some(stdout) = child.stdout.take() {
+        forward_lines(stdout, sender.clone());
+    }
+    if let Some(stderr) = child.stderr.take() {
+        forward_lines(stderr, sender);
+    }
+    let timeout = test.timeout.unwrap_or(DEFAULT_READY_TIMEOUT);
+    let mut output = vec![];
+    let ready = tokio::time::timeout(
+        timeout,
+        wait_ready(&probe, pattern, &mut child, &mut lines, &mut output),
+    )
+    .await;
+    let result = match ready {
+        Ok(Ok(())) => Ok(()),
+        Ok(Err(exit_code)) => {
+            test.exit_code = exit_code;
+            Err(anyhow::anyhow!(
+                "The daemon '{}' has exited before it was ready, with exit code: {:?}",
+                test.name,
+                exit_code
+            ))
+        }
+        Err(_) => Err(timed_out(test, timeout)),
+    };
+    test.output = Some(output.join("\n"));
+    result.map(|_| DaemonProcess {
+        _guard: guard,
+        _child: child,
+    })
+}
+
+/// Waits until the probe succeeds (or a line matches the log pattern),
+/// the error is the exit code if the daemon exits before.
+async fn wait_ready(
+    probe: &ReadyProbe,
+    pattern: Option<Regex>,
+    child: &mut Child,
+    lines: &mut UnboundedReceiver<String>,
+    output: &mut Vec<String>,
+) -> Result<(), Option<i32>> {
+    let mut interval = tokio::time::interval(PROBE_INTERVAL);
+    loop {
+        tokio::select! {
+            status = child.wait() => {
+                // the last lines may still be in the channel
+                let _ = tokio::time::timeout(PROBE_INTERVAL, async {
+                    while let Some(line) = lines.recv().await {
+                        output.push(line);
+                    }
+                })
+                .await;
+                return Err(status.ok().and_then(|s| s.code()));
+            }
+            Some(line) = lines.recv() => {
+                let matched = pattern.as_ref().is_some_and(|p| p.is_match(&line));
+                output.push(line);
+                if matched {
+                    return Ok(());
+                }
+            }
+            _ = interval.tick(), if pattern.is_none() => {
+                if is_ready(probe).await {
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
+
+/// Runs the TCP or HTTP probe once.
+async fn is_ready(probe: &ReadyProbe) -> bool {
+    match probe {
+        ReadyProbe::Tcp(address) => TcpStream::connect(address).await.is_ok(),
+        ReadyProbe::Http(url) => http_ok(url).await.unwrap_or(false),
+        ReadyProbe::Log(_) => false,
+    }
+}
+
+/// The host (for the `Host` header) and the `host:port` address to connect to,
+/// from the URL authority, e.g: `localhost:3000`, `[::1]` or `user@[::1]:3000`.
+fn socket_address(authority: &str) -> Result<(&str, String)> {
+    let host_port = authority.rsplit_once('@').map_or(authority, |(_, h)| h);
+    let (host, port) = match host_port.strip_prefix('[') {
+        // an IPv6 address, the port (if any) is after the brackets
+        Some(ipv6) => {
+            let end = ipv6
+                .find(']')
+                .ok_or_else(|| anyhow::anyhow!("Invalid IPv6 host in '{}'", authority))?;
+            let port = match &ipv6[end + 1..] {
+                "" => None,
+                rest => Some(rest.strip_prefix(':').ok_or_else(|| {
+                    anyhow::anyhow!("Invalid port after the IPv6 host in '{}'", authority)
+                })?),
+            };
+            (&host_port[..end + 2], port)
+        }
+        None => match host_port.split_once(':') {
+            Some((host, port)) => (host, Some(port)),
+            None => (host_port, None),
+        },
+    };
+    let port = match port {
+        Some(port) if !port.is_empty() => port
+            .parse::<u16>()
+            .map_err(|_| anyhow::anyhow!("Invalid port '{}' in '{}'", port, authority))?,
+        _ => 80,
+    };
+    Ok((host_port, format!("{}:{}", host, port)))
+}
+
+/// Performs a plain HTTP/1.1 GET, true if the response status is 200.
+///
+/// Only `http://` URLs are supported, the daemons are expected to run locally.
+async fn http_ok(url: &str) -> Result<bool> {
+    let rest = url
+        .strip_prefix("http://")
+        .ok_or_else(|| anyhow::anyhow!("Only http:// URLs are supported, got '{}'", url))?;
+    let (authority, path) = match rest.find(['/', '?', '#']) {
+        Some(i) if rest[i..].starts_with('/') => (&rest[..i], &rest[i..]),
+        Some(i) => (&rest[..i], "/"),
+        None => (rest, "/"),
+    };
+    let (host, address) = socket_address(authority)?;
+    let mut stream = TcpStream::connect(address).await?;
+    let request = format!(
+        "GET {} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\n\r\n",
+        path, host
+    );
+    stream.write_all(request.as_bytes()).await?;
+    let mut status_line = String::new();
+    BufReader::new(stream).read_line(&mut status_line).await?;
+    Ok(status_line.split_whitespace().nth(1) == Some("200"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::socket_address;
+
+    #[test]
+    fn assert_socket_address_from_authority() {
+        let address = |authority| socket_address(authority).unwrap().1;
+        assert_eq!(address("localhost"), "localhost:80");
+        assert_eq!(address("localhost:3000"), "localhost:3000");
+        assert_eq!(address("user:secret@localhost:3000"), "localhost:3000");
+        assert_eq!(address("[::1]"), "[::1]:80");
+        assert_eq!(address("[::1]:3000"), "[::1]:3000");
+        assert_eq!(socket_address("[::1]:3000").unwrap().0, "[::1]:3000");
+        assert!(socket_address("[::1").is_err());
+        assert!(socket_address("[::1]3000").is_err());
+        assert!(socket_address("localhost:http").is_err());
+    }
+}
@@ -0,0 +1,152 @@
+use std::{path::Path, str::FromStr};
+
+use anyhow::{anyhow, Result};
+use prost::Message;
+use prost_reflect::{DynamicMessage, MessageDescriptor, MethodDescriptor};
+use tonic::{
+    codec::{Codec, DecodeBuf, Decoder, EncodeBuf, Encoder},
+    codegen::http::uri::PathAndQuery,
+    metadata::{MetadataKey, MetadataValue},
+    transport::Endpoint,
+    Status,
+};
+
+use crate::entities::graph::GrpcRequest;
+
+/// A tonic codec that works with messages only known at runtime.
+///
+/// Any `DynamicMessage` can be encoded, the decoding uses the descriptor provided on creation.
+/// For a client, it is the method output descriptor, for a server, the method input descriptor.
+#[derive(Debug, Clone)]
+pub struct DynamicCodec(MessageDescriptor);
+
+#[derive(Debug, Clone)]
+pub struct DynamicEncoder;
+
+#[derive(Debug, Clone)]
+pub struct DynamicDecoder(MessageDescriptor);
+
+impl DynamicCodec {
+    pub fn new(decode_descriptor: MessageDescriptor) -> Self {
+        Self(decode_descriptor)
+    }
+}
+
+impl Codec for DynamicCodec {
+    type Encode = DynamicMessage;
+    type Decode = DynamicMessage;
+    type Encoder = DynamicEncoder;
+    type Decoder = DynamicDecoder;
+
+    fn encoder(&mut self) -> Self::Encoder {
+        DynamicEncoder
+    }
+
+    fn decoder(&mut self) -> Self::Decoder {
+        DynamicDecoder(self.0.clone())
+    }
+}
+
+impl Encoder for DynamicEncoder {
+    type Item = DynamicMessage;
+    type Error = Status;
+
+    fn encode(&mut self, item: Self::Item, dst: &mut EncodeBuf<'_>) -> Result<(), Self::Error> {
+        item.encode(dst)
+            .map_err(|err| Status::internal(err.to_string()))
+    }
+}
+
+impl Decoder for DynamicDecoder {
+    type Item = DynamicMessage;
+    type Error = Status;
+
+    fn decode(&mut self, src: &mut DecodeBuf<'_>) -> Result<Option<Self::Item>, Self::Error> {
+        DynamicMessage::decode(self.0.clone(), src)
+            .map(Some)
+            .map_err(|err| Status::internal(err.to_string()))
+    }
+}
+
+/// Compiles the proto file and finds the method descriptor.
+///
+/// The proto imports are resolved relative to the proto file directory and to the current directory,
+/// like `grpcurl -import-path .` does.
+///
+/// The method can be written as `package.Service/Method` or `package.Service.Method`.
+pub fn load_method(proto: &str, method: &str) -> Result<MethodDescriptor> {
+    let parent = Path::new(proto)
+        .parent()
+        .filter(|p| !p.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    let mut compiler = protox::Compiler::new([parent, Path::new(".")])?;
+    compiler.open_file(proto)?;
+    let pool = compiler.descriptor_pool();
+
+    let (service_name, method_name) = method
+        .rsplit_once('/')
+        .or_else(|| method.rsplit_once('.'))
+        .ok_or_else(|| {
+            anyhow!(
+                "Invalid gRPC method '{}', expected <service>/<method>",
+                method
+            )
+        })?;
+    let service = pool
+        .get_service_by_name(service_name)
+        .ok_or_else(|| anyhow!("Service '{}' not found in '{}'", service_name, proto))?;
+    let found = service.methods().find(|m| m.name() == method_name);
+    found.ok_or_else(|| {
+        anyhow!(
+            "Method '{}' not found in service '{}'",
+            method_name,
+            service_name
+        )
+    })
+}
+
+/// Performs a unary gRPC call and returns the response message as JSON.
+///
+/// When the server answers with a non OK status, the `tonic::Status` is returned inside the error,
+/// so the caller can retrieve it with `downcast_ref`.
+pub async fn unary_call(request: &GrpcRequest) -> Result<String> {
+    let method = load_method(&request.proto, &request.method)?;
+    let body = unescape::unescape(&request.body).unwrap_or_else(|| request.body.clone());
+    let message = match body.trim().is_empty() {
+        true => DynamicMessage::new(method.input()),
+        false => {
+            let mut deserializer = serde_json::Deserializer::from_str(&body);
+            let message = DynamicMessage::deserialize(method.input(), &mut deserializer)?;
+            deserializer.end()?;
+            message
+        }
+    };
+
+    let mut grpc_request = tonic::Request::new(message);
+    for header in request.headers.iter() {
+        let (key, value) = header
+            .split_once(':')
+            .ok_or_else(|| anyhow!("Invalid header '{}', expected <key>: <value>", header))?;
+        grpc_request.metadata_mut().insert(
+            MetadataKey::from_str(&key.trim().to_lowercase())?,
+            MetadataValue::from_str(value.trim())?,
+        );
+    }
+
+    let address = match request.address.contains("://") {
+        true => request.address.clone(),
+        false => format!("http://{}", request.address),
+    };
+    let channel = Endpoint::from_shared(address)?.connect().await?;
+    let mut client = tonic::client::Grpc::new(channel);
+    client.ready().await?;
+    let path = PathAndQuery::from_str(&format!(
+        "/{}/{}",
+        method.parent_service().full_name(),
+        method.name()
+    ))?;
+    let response = client
+        .unary(grpc_request, path, DynamicCodec::new(method.output()))
+        .await?;
+    Ok(serde_json::to_string_pretty(response.get_ref())?)
+}
@@ -1,2 +1,7 @@
+pub mod assertions;
+pub mod daemons;
+pub mod grpc_client;
 pub mod node_info;
-pub mod test_executable;
\ No newline at end of file
+pub mod retries;
+pub mod templates;
+pub mod test_executable;
@@ -1,18 +1,26 @@
-use crate::{db::SqliteStorage, entities::api::TestNodeInfo, traits::Storage};
+use crate::{
+    entities::{api::TestNodeInfo, storage::NodeHistory},
+    traits::Storage,
+};
 use anyhow::Result;
 
-pub fn get_node_info(node_id: i32) -> Result<TestNodeInfo> {
-    let db = SqliteStorage::new();
+/// A flaky test has completed, but only after being retried.
+fn is_flaky(history: &[NodeHistory]) -> bool {
+    history.iter().any(|h| h.status == "Retrying")
+        && history.iter().any(|h| h.status == "Completed")
+}
+
+pub fn get_node_info(db: &dyn Storage, run: i64, node_id: i32) -> Result<TestNodeInfo> {
     let node = db
-        .get_nodes(&[node_id])?
+        .get_nodes(run, &[node_id])?
         .first()
         .cloned()
         .ok_or(anyhow::anyhow!("Node not found"))?;
-    let history = db.get_node_history(node_id)?;
-    let processed_history = db.get_processed_node_history(node.id)?;
+    let history = db.get_node_history(run, node_id)?;
+    let processed_history = db.get_processed_node_history(run, node.id)?;
     let data = history
         .iter()
-        .find(|h| ["Completed", "Failed"].contains(&h.status.as_str()))
+        .find(|h| ["Completed", "Failed", "AssertionFailed"].contains(&h.status.as_str()))
         .map(|h| h.data.clone())
         .unwrap_or_default();
     let node_info = TestNodeInfo {
@@ -22,22 +30,22 @@ pub fn get_node_info(node_id: i32) -> Result<TestNodeInfo> {
         name: node.name.clone(),
         description: node.description.clone(),
         service: node.service.clone(),
+        flaky: is_flaky(&history),
         data,
     };
 
     Ok(node_info)
 }
 
-pub fn get_nodes_info() -> Result<Vec<TestNodeInfo>> {
-    let db = SqliteStorage::new();
-    let nodes = db.get_all_nodes()?;
+pub fn get_nodes_info(db: &dyn Storage, run: i64) -> Result<Vec<TestNodeInfo>> {
+    let nodes = db.get_all_nodes(run)?;
     let mut nodes_info = vec![];
     for node in nodes {
-        let history = db.get_node_history(node.id)?;
-        let processed_history = db.get_processed_node_history(node.id)?;
+        let history = db.get_node_history(run, node.id)?;
+        let processed_history = db.get_processed_node_history(run, node.id)?;
         let data = history
             .iter()
-            .find(|h| ["Completed", "Failed"].contains(&h.status.as_str()))
+            .find(|h| ["Completed", "Failed", "AssertionFailed"].contains(&h.status.as_str()))
             .map(|h| h.data.clone())
             .unwrap_or_default();
         nodes_info.push(TestNodeInfo {
@@ -47,6 +55,7 @@ pub fn get_nodes_info() -> Result<Vec<TestNodeInfo>> {
             name: node.name.clone(),
             description: node.description.clone(),
             service: node.service.clone(),
+            flaky: is_flaky(&history),
             data,
         });
     }
@@ -0,0 +1,37 @@
+use std::time::Duration;
+
+use regex::Regex;
+
+use crate::entities::{enums::TestStatus, graph::TestExecutable};
+
+/// Checks if the test must be retried after the attempt (starting from 1) that ended with the status.
+///
+/// Only failures are retried, while there are retries left and the `retry_on` conditions match.
+pub fn should_retry(test: &TestExecutable, status: TestStatus, attempt: u32) -> bool {
+    let failed = matches!(status, TestStatus::Failed | TestStatus::AssertionFailed);
+    if !failed || attempt > test.retries {
+        return false;
+    }
+    let retry_on = match &test.retry_on {
+        Some(retry_on) => retry_on,
+        None => return true,
+    };
+    let exit_code_matches = test
+        .exit_code
+        .map(|code| retry_on.exit_codes.contains(&code))
+        .unwrap_or(false);
+    let output_matches = match (&retry_on.output, &test.output) {
+        (Some(pattern), Some(output)) => Regex::new(pattern)
+            .map(|regex| regex.is_match(output))
+            .unwrap_or(false),
+        _ => false,
+    };
+    exit_code_matches || output_matches
+}
+
+/// The delay before the retry that follows the attempt (starting from 1),
+/// the `retry_delay` is doubled after each retry.
+pub fn retry_delay(test: &TestExecutable, attempt: u32) -> Duration {
+    let delay = test.retry_delay.unwrap_or_default();
+    delay.saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+}
@@ -0,0 +1,64 @@
+use std::collections::HashMap;
+
+use anyhow::{anyhow, Result};
+use regex::{Captures, Regex};
+
+use crate::entities::graph::TestNode;
+
+/// Placeholders like `{{ foo.test1.output }}` or `{{ foo.test1.exit_code }}`,
+/// where `foo.test1` is the full test id (`<service>.<test id>`).
+const TEMPLATE_PATTERN: &str = r"\{\{\s*(?P<id>[^{}\s]+)\.(?P<field>output|exit_code)\s*\}\}";
+
+/// A reference to another test result, found in a template placeholder.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TemplateRef {
+    pub test_id: String,
+    pub field: String,
+}
+
+fn template_regex() -> Regex {
+    Regex::new(TEMPLATE_PATTERN).unwrap()
+}
+
+/// Finds all test references in the text.
+pub fn references(text: &str) -> Vec<TemplateRef> {
+    template_regex()
+        .captures_iter(text)
+        .map(|c| TemplateRef {
+            test_id: c["id"].to_string(),
+            field: c["field"].to_string(),
+        })
+        .collect()
+}
+
+/// Replaces all placeholders with the results of the tests in the context (indexed by test id).
+///
+/// The output trailing newlines are removed, like a shell `$(...)` substitution does.
+///
+/// It fails if a referenced test is not in the context or has no result yet.
+pub fn render(text: &str, context: &HashMap<String, &TestNode>) -> Result<String> {
+    let mut error = None;
+    let rendered = template_regex().replace_all(text, |c: &Captures| {
+        let value = context.get(&c["id"]).and_then(|node| match &c["field"] {
+            "output" => node
+                .executable
+                .output
+                .as_ref()
+                .map(|o| o.trim_end_matches(['\n', '\r']).to_string()),
+            _ => node.executable.exit_code.map(|code| code.to_string()),
+        });
+        value.unwrap_or_else(|| {
+            error = Some(anyhow!(
+                "The test '{}' has no {} to be used in '{}'",
+                &c["id"],
+                &c["field"],
+                &c[0]
+            ));
+            String::new()
+        })
+    });
+    match error {
+        Some(err) => Err(err),
+        None => Ok(rendered.into_owned()),
+    }
+}
@@ -1,17 +1,110 @@
-use crate::entities::graph::TestExecutable;
+use std::{os::unix::process::CommandExt, process::Stdio, time::Duration};
+
+use crate::{entities::graph::TestExecutable, services::grpc_client::unary_call};
 use anyhow::Result;
 use tokio::process::Command;
 
-pub async fn grpc_call(_test: &mut TestExecutable) -> Result<()> {
-    todo!()
+/// Marks the test as timed out, it has no exit code neither output.
+pub(crate) fn timed_out(test: &mut TestExecutable, timeout: Duration) -> anyhow::Error {
+    let reason = format!(
+        "timed out after {}",
+        humantime::format_duration(Duration::from_millis(timeout.as_millis() as u64))
+    );
+    test.exit_code = None;
+    test.output = None;
+    test.reason = Some(reason.clone());
+    anyhow::anyhow!("The test '{}' {}", test.name, reason)
+}
+
+/// Kills the process and all of its children, they share the same process group.
+fn kill_process_group(pid: Option<u32>) {
+    if let Some(pid) = pid {
+        // SAFETY: killpg only sends a signal, it doesn't touch any memory of this process.
+        // The pid is the group id of the spawned command (it is the leader of its own group),
+        // at worst the group is already gone and the call fails with ESRCH.
+        unsafe {
+            libc::killpg(pid as libc::pid_t, libc::SIGKILL);
+        }
+    }
+}
+
+/// Kills the process group when dropped, unless the process has finished.
+///
+/// The call future is dropped when the run is cancelled, so the whole process tree is killed with it.
+pub(crate) struct ProcessGroupGuard(pub(crate) Option<u32>);
+
+impl ProcessGroupGuard {
+    pub(crate) fn finished(mut self) {
+        self.0 = None;
+    }
+}
+
+impl Drop for ProcessGroupGuard {
+    fn drop(&mut self) {
+        kill_process_group(self.0);
+    }
+}
+
+pub async fn grpc_call(test: &mut TestExecutable) -> Result<()> {
+    let request = test.grpc.clone().ok_or_else(|| {
+        anyhow::anyhow!("The test '{}' has no gRPC request to perform", test.name)
+    })?;
+    let response = match test.timeout {
+        Some(timeout) => match tokio::time::timeout(timeout, unary_call(&request)).await {
+            Ok(response) => response,
+            Err(_) => return Err(timed_out(test, timeout)),
+        },
+        None => unary_call(&request).await,
+    };
+    match response {
+        Ok(response) => {
+            test.exit_code = Some(tonic::Code::Ok as i32);
+            test.output = Some(response);
+            Ok(())
+        }
+        Err(err) => {
+            // Non OK statuses keep their gRPC code as exit code,
+            // any other error (proto, connection, etc) has no exit code at all.
+            match err.downcast_ref::<tonic::Status>() {
+                Some(status) => {
+                    test.exit_code = Some(status.code() as i32);
+                    test.output = Some(status.message().to_string());
+                }
+                None => {
+                    test.exit_code = None;
+                    test.output = Some(err.to_string());
+                }
+            };
+            Err(anyhow::anyhow!(
+                "The test '{}' failed with message: {:?}",
+                test.name,
+                test.output
+            ))
+        }
+    }
 }
 
 pub async fn scripts_call(test: &mut TestExecutable) -> Result<()> {
-    let output = Command::new("sh")
-        .arg("-c")
-        .arg(&test.command)
-        .output()
-        .await?;
+    // The script runs in its own process group, so the whole process tree
+    // can be killed when it times out.
+    let mut command = std::process::Command::new("sh");
+    command.arg("-c").arg(&test.command).process_group(0);
+    let child = Command::from(command)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()?;
+    let guard = ProcessGroupGuard(child.id());
+    let output = match test.timeout {
+        Some(timeout) => match tokio::time::timeout(timeout, child.wait_with_output()).await {
+            Ok(output) => output?,
+            // the guard kills the process group
+            Err(_) => return Err(timed_out(test, timeout)),
+        },
+        None => child.wait_with_output().await?,
+    };
+    guard.finished();
     test.exit_code = output.status.code();
     test.output = match output.status.success() {
         true => String::from_utf8(output.stdout)
@@ -2,14 +2,13 @@ use anyhow::Result;
 use petgraph::{prelude::DiGraph, stable_graph::NodeIndex};
 
 use crate::entities::{
-    enums::TestStatus,
+    enums::{DependencyCondition, TestStatus},
     graph::FilterOptions,
-    storage::{DbGraph, DbNode, NodeHistory, ProcessedHistory},
+    storage::{DbGraph, DbNode, DbRun, NodeHistory, ProcessedHistory, StorageResult},
 };
 
 use super::entities::graph::TestNode;
 
-
 /// Base trait for the Graph, this trait specifies the methods necessary to use the graph as a workflow by the test runners.
 pub trait GraphWorkflow {
     /// Check if the graph is cyclic.
@@ -20,10 +19,12 @@ pub trait GraphWorkflow {
     ///
     /// This check is mandatory to assert the Graph integrity.
     /// If the graph is cyclic, then it will return all nodes in topological order as a Vec<&TestNode>.
-    /// An error otherwise.
+    /// An error otherwise, listing every cycle path and where each dependency was declared.
     fn is_cyclic(&self) -> Result<Vec<&TestNode>>;
     /// Without any criteria, get all Graph orphan nodes
     fn orphan_nodes(&self) -> Vec<&TestNode>;
+    /// Get all nodes that the node depends on, directly or indirectly.
+    fn ancestors(&self, node_idx: NodeIndex) -> Vec<&TestNode>;
     /// Get all tests that are available to be run.
     ///
     /// The main idea is to get all tests that are not started and all of its dependencies (if present) are completed.
@@ -37,7 +38,9 @@ pub trait GraphWorkflow {
     /// The approach is to always retrieve all orphan nodes.
     /// Orphan nodes in the child graph means that the test has no dependencies or all tests that are required for it are completed.
     ///
-    /// The `availables` method will always return the child graph orphan nodes.
+    /// The orphans whose dependencies are still running are left out,
+    /// so a node is available as soon as all of its dependencies are completed,
+    /// even while other tests are still running.
     fn availables(&self) -> Result<Vec<TestNode>>;
     /// Filters in the Workflow graph and returns a new graph with the filtered nodes as referencies.
     ///
@@ -52,12 +55,12 @@ pub trait GraphWorkflow {
     ///
     /// use thorust::traits::GraphWorkflow;
     /// use thorust::entities::graph::{FilterOptions, TestNode};
-    /// use thorust::entities::enums::TestStatus;
+    /// use thorust::entities::enums::{DependencyCondition, TestStatus};
     /// use thorust::entities::graph::TestExecutable;
     /// use thorust::workflow::Workflow;
     ///
     /// fn main() -> Result<()> {
-    ///   let mut graph = DiGraph::<TestNode, usize>::new();
+    ///   let mut graph = DiGraph::<TestNode, DependencyCondition>::new();
     ///   let a = graph.add_node(TestNode {
     ///         id: "a".to_string(),
     ///         index: 0,
@@ -79,8 +82,8 @@ pub trait GraphWorkflow {
     ///         depends_on: vec![],
     ///         executable: TestExecutable::default(),
     ///     });
-    ///     graph.add_edge(a, b, 0);
-    ///     graph.add_edge(b, c, 0);
+    ///     graph.add_edge(a, b, DependencyCondition::Success);
+    ///     graph.add_edge(b, c, DependencyCondition::Success);
     ///
     ///     let workflow = Workflow::from_graph(graph);
     ///     let not_started = workflow.filter_graph(FilterOptions::not_started());
@@ -92,12 +95,14 @@ pub trait GraphWorkflow {
     ///
     ///     Ok(())
     /// }
-    fn filter_graph(&self, filter: FilterOptions) -> DiGraph<&TestNode, &usize>;
+    fn filter_graph(&self, filter: FilterOptions) -> DiGraph<&TestNode, &DependencyCondition>;
     /// This method should update the node in the graph and refresh the graph state by the last status of the node.
     ///
     /// Internally it uses the `update_node` method to update the node with the new state.
     ///
-    /// If the test fails or are skipped, it should mark the tests that depends on him as Skipped.
+    /// When the test reaches a final status, the tests that depends on him through a condition
+    /// that can't be satisfied anymore are marked as Skipped (see `DependencyCondition`).
+    /// By default, the condition is `success`, so failed or skipped tests skip their dependents.
     ///
     /// The callback function is called after each graph change.
     ///
@@ -106,16 +111,18 @@ pub trait GraphWorkflow {
     ///    * dot: The dot representation of the graph after the change
     ///
     /// **Important:**
-    /// The attribution is recursive and uses a depth-first-search to update all nodes that share their path.
+    /// The attribution is recursive, a skipped test also skips its own dependents.
     ///
     /// I.e.: `a->b->c->d`.
     /// * if `a` fails: `b`, `c` and `d` will be marked as skipped.
     /// * if `b` fails: `c` and `d` will be marked as skipped.
     /// * if `a` completes: `b`, `c` and `d` will not be changed, staying available to run in the next iteration.
+    /// * if `b` fails and `c` depends on `b` with `on: failure` (or `on: always`): `c` becomes available
+    ///   and `d` is not changed, since it only depends on `c`.
     fn update_graph_state(
         &mut self,
         node: TestNode,
-        callback: impl Fn(&TestNode, &str) + Send + Copy + 'static,
+        callback: impl Fn(&TestNode, &str) + Send + Clone + 'static,
     );
     /// Updates a single node status
     ///
@@ -137,6 +144,8 @@ pub trait GraphWorkflow {
         callback: impl Fn(&TestNode, &str) + Send + 'static,
     ) -> bool;
     /// Get Dot graphviz representation of the graph
+    ///
+    /// The setup and teardown hooks are drawn as dashed boxes.
     fn as_dot(&self) -> String;
     /// Get Json graphviz representation of the graph
     fn as_json(&self) -> String;
@@ -144,15 +153,19 @@ pub trait GraphWorkflow {
     fn reset(&mut self) -> Result<()>;
 }
 
-
 /// Base trait for Runners, this trait specifies the methods responsible to run the Test nodes.
 #[async_trait::async_trait]
 pub trait RunnerWorkflow {
     /// Runs a single test node.
+    ///
+    /// A daemon is stopped as soon as it is ready, as the run is over.
     async fn execute(&mut self, node: TestNode) -> Result<String>;
-    /// Batch execute, spawn threads for each test.
+    /// Batch execute, spawn threads for each test, respecting the concurrency limits.
     async fn batch_execute(&mut self, nodes: Vec<TestNode>) -> Result<()>;
-    /// Loop over all available tests running them until no more tests are available to be run.
+    /// Runs all available tests until no more tests are available to be run.
+    ///
+    /// Each test starts as soon as all of its dependencies are completed,
+    /// without waiting for the other running tests.
     async fn run_until_complete(&mut self) -> Result<()>;
     /// Reset the internal state (workflow and storage) to its initial state.
     ///
@@ -160,26 +173,44 @@ pub trait RunnerWorkflow {
     async fn reset(&mut self) -> Result<()>;
 }
 
-/// Base Storage trait that needs to be implemeted by all storages.
+/// Base Storage trait that needs to be implemeted by all storages: sqlite, json lines, in memory, etc...
+///
+/// The errors are `StorageError`, whatever the backend is.
 pub trait Storage: Send + Sync {
-    fn insert_test_node(&self, node: &TestNode);
-    fn insert_node(&self, node: DbNode) -> i64;
-    fn insert_node_history(&self, status: &str, node_id: i64, data: &str) -> i64;
-    fn insert_dot(&self, dot: &str) -> i64;
-    fn get_nodes(&self, ids: &[i32]) -> rusqlite::Result<Vec<DbNode>>;
-    fn get_node_history(&self, node_id: i32) -> rusqlite::Result<Vec<NodeHistory>>;
-    fn get_dots(&self) -> rusqlite::Result<Vec<DbGraph>>;
-    fn insert_test_nodes(&self, nodes: Vec<&TestNode>);
-    fn get_processed_node_history(&self, node_id: i32) -> rusqlite::Result<Vec<ProcessedHistory>>;
-    fn get_all_processed_node_history(&self) -> rusqlite::Result<Vec<ProcessedHistory>>;
-    fn get_all_nodes(&self) -> rusqlite::Result<Vec<DbNode>>;
+    /// Starts a new run of the given manifest, returns the run id
+    /// that scopes the nodes, their history and the graphs.
+    fn insert_run(&self, manifest: &str) -> StorageResult<i64>;
+    /// Marks the run as finished, with its final counts.
+    fn finish_run(&self, run: &DbRun) -> StorageResult<()>;
+    fn get_runs(&self) -> StorageResult<Vec<DbRun>>;
+    fn get_last_run(&self) -> StorageResult<Option<DbRun>>;
+    fn insert_test_node(&self, run: i64, node: &TestNode) -> StorageResult<()>;
+    fn insert_node(&self, run: i64, node: DbNode) -> StorageResult<i64>;
+    fn insert_node_history(
+        &self,
+        run: i64,
+        status: &str,
+        node_id: i64,
+        data: &str,
+    ) -> StorageResult<i64>;
+    fn insert_dot(&self, run: i64, dot: &str) -> StorageResult<i64>;
+    fn get_nodes(&self, run: i64, ids: &[i32]) -> StorageResult<Vec<DbNode>>;
+    fn get_node_history(&self, run: i64, node_id: i32) -> StorageResult<Vec<NodeHistory>>;
+    fn get_dots(&self, run: i64) -> StorageResult<Vec<DbGraph>>;
+    fn insert_test_nodes(&self, run: i64, nodes: Vec<&TestNode>) -> StorageResult<()>;
+    fn get_processed_node_history(
+        &self,
+        run: i64,
+        node_id: i32,
+    ) -> StorageResult<Vec<ProcessedHistory>>;
+    fn get_all_processed_node_history(&self, run: i64) -> StorageResult<Vec<ProcessedHistory>>;
+    fn get_all_nodes(&self, run: i64) -> StorageResult<Vec<DbNode>>;
 }
 
-
 /// Base trait that needs to be implemented by all manifest types: scripts, grpc, http, etc...
 pub trait Manifest {
     /// Checks the integrity and normalize the parsed manifest content.
-    /// 
+    ///
     /// This is particularly usefull to prevent many issues, such as:
     /// * prevent ids that doesn't exists for being used in depends_on tag
     /// * normalize the tests with valid format ids, e.g: `<service name>.<test id>`
@@ -189,4 +220,4 @@ pub trait Manifest {
 
     /// Converts all tests defined in the manifest into an array of TestNode
     fn as_test_nodes(&self) -> Result<Vec<TestNode>>;
-}
\ No newline at end of file
+}
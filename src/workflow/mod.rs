@@ -1,9 +1,11 @@
+use std::collections::HashSet;
+
 use anyhow::Result;
 use petgraph::{
     dot::{Config, Dot},
     prelude::DiGraph,
     stable_graph::NodeIndex,
-    visit::Dfs,
+    visit::{Dfs, EdgeRef, Reversed},
 };
 
 use crate::{
@@ -12,14 +14,30 @@ use crate::{
 };
 
 use super::{
-    entities::{enums::TestStatus, graph::TestNode},
+    entities::{
+        enums::{DependencyCondition, HookKind, TestStatus},
+        graph::TestNode,
+    },
     traits::GraphWorkflow,
 };
 
+/// The most cycles reported when the workflow is cyclic.
+pub const MAX_CYCLES: usize = 20;
+
 #[derive(Clone)]
 pub struct Workflow {
-    pub graph: DiGraph<TestNode, usize>,
+    pub graph: DiGraph<TestNode, DependencyCondition>,
     manifest: Option<BaseManifest>,
+    /// Applied again when the workflow is reset
+    selection: Option<Selection>,
+}
+
+/// Which tests to run: the ones matching `include` and not matching `exclude`,
+/// e.g: `run --tags smoke --only 'foo.*' --exclude foo.slow`
+#[derive(Debug, Clone, Default)]
+pub struct Selection {
+    pub include: FilterOptions,
+    pub exclude: Option<FilterOptions>,
 }
 
 impl Workflow {
@@ -27,31 +45,225 @@ impl Workflow {
         Ok(Self {
             graph: manifest.clone().try_into()?,
             manifest: Some(manifest),
+            selection: None,
         })
     }
-    pub fn from_graph(graph: DiGraph<TestNode, usize>) -> Self {
+    pub fn from_graph(graph: DiGraph<TestNode, DependencyCondition>) -> Self {
         Self {
             graph,
             manifest: None,
+            selection: None,
+        }
+    }
+
+    /// Keeps only the selected tests in the graph, the nodes are indexed again.
+    ///
+    /// The ancestors of the selected tests are kept as well, even if they are excluded,
+    /// so the depends_on contracts still hold.
+    /// The teardowns are kept if any of the tests (or daemons) they clean up is kept.
+    pub fn select(&mut self, selection: Selection) -> Result<()> {
+        self.apply_selection(&selection)?;
+        self.selection = Some(selection);
+        Ok(())
+    }
+
+    /// Finds the node by its test id, or by its index if the target is a number.
+    pub fn find_node(&self, target: &str) -> Option<&TestNode> {
+        let filter = match target.parse::<u32>() {
+            Ok(index) => FilterOptions {
+                index: Some(index),
+                ..Default::default()
+            },
+            Err(_) => FilterOptions {
+                id: Some(target.to_string()),
+                ..Default::default()
+            },
+        };
+        self.filter_graph(filter).node_weights().next().copied()
+    }
+
+    /// Resets the nodes to their initial state, so they can run again,
+    /// e.g: their templates are rendered again with the new results.
+    ///
+    /// Without a manifest, only their status is reset.
+    pub fn reset_nodes(&mut self, indexes: &HashSet<u32>) -> Result<()> {
+        let initial = match self.manifest {
+            Some(_) => {
+                let mut initial = self.clone();
+                initial.reset()?;
+                Some(initial.graph)
+            }
+            None => None,
+        };
+        for index in indexes.iter() {
+            let node_idx = NodeIndex::new(*index as usize);
+            match &initial {
+                Some(initial) => self.graph[node_idx] = initial[node_idx].clone(),
+                None => self.graph[node_idx].status = vec![TestStatus::NotStarted],
+            }
+        }
+        Ok(())
+    }
+
+    fn apply_selection(&mut self, selection: &Selection) -> Result<()> {
+        let excluded = |node: &TestNode| {
+            selection
+                .exclude
+                .as_ref()
+                .is_some_and(|exclude| exclude.check(node))
+        };
+        // A teardown depends on all the tests it cleans up, so it is never selected by itself,
+        // otherwise all of them would be selected as its ancestors.
+        let mut selected: HashSet<NodeIndex> = self
+            .graph
+            .node_indices()
+            .filter(|i| {
+                let node = &self.graph[*i];
+                node.executable.hook != Some(HookKind::Teardown)
+                    && selection.include.check(node)
+                    && !excluded(node)
+            })
+            .collect();
+        if selected.is_empty() {
+            return Err(anyhow::anyhow!("No test matches the selection"));
+        }
+        for i in selected.clone() {
+            selected.extend(
+                self.ancestors(i)
+                    .iter()
+                    .map(|node| NodeIndex::new(node.index as usize)),
+            );
+        }
+        let teardowns = self
+            .graph
+            .node_indices()
+            .filter(|i| {
+                let node = &self.graph[*i];
+                node.executable.hook == Some(HookKind::Teardown)
+                    && !excluded(node)
+                    && self
+                        .graph
+                        .neighbors_directed(*i, petgraph::Direction::Incoming)
+                        .any(|dep| {
+                            selected.contains(&dep) && self.graph[dep].executable.hook.is_none()
+                        })
+            })
+            .collect::<Vec<NodeIndex>>();
+        selected.extend(teardowns);
+        let mut graph = self.graph.filter_map(
+            |i, node| selected.contains(&i).then(|| node.clone()),
+            |_, edge| Some(*edge),
+        );
+        // the node index must match its new graph index
+        let ids: HashSet<String> = graph.node_weights().map(|n| n.id.clone()).collect();
+        for i in graph.node_indices() {
+            let node = &mut graph[i];
+            node.index = i.index() as u32;
+            node.depends_on.retain(|dep| ids.contains(dep));
+            node.executable
+                .conditions
+                .retain(|dep, _| ids.contains(dep));
+        }
+        self.graph = graph;
+        Ok(())
+    }
+
+    /// Finds the elementary cycles of the tests that depend on each other,
+    /// following the depends_on direction: `[a, b]` means a depends on b and b depends on a.
+    ///
+    /// A group of tests can have an exponential number of cycles,
+    /// so the search stops after [`MAX_CYCLES`].
+    pub fn cycles(&self) -> Vec<Vec<NodeIndex>> {
+        let mut cycles = vec![];
+        for mut scc in petgraph::algo::tarjan_scc(&self.graph) {
+            scc.sort();
+            // each cycle is searched from its lowest node, so it is only found once
+            for (i, &start) in scc.iter().enumerate() {
+                if cycles.len() >= MAX_CYCLES {
+                    break;
+                }
+                self.find_cycles(&scc[i..], &mut vec![start], &mut cycles);
+            }
+        }
+        cycles.sort();
+        cycles
+    }
+
+    /// Walks over the dependencies (incoming edges) of the last node of the path, inside the
+    /// allowed nodes, adding the path as a cycle whenever it gets back to its first node.
+    fn find_cycles(
+        &self,
+        allowed: &[NodeIndex],
+        path: &mut Vec<NodeIndex>,
+        cycles: &mut Vec<Vec<NodeIndex>>,
+    ) {
+        let current = *path.last().unwrap();
+        let mut deps = self
+            .graph
+            .neighbors_directed(current, petgraph::Direction::Incoming)
+            .filter(|n| allowed.contains(n))
+            .collect::<Vec<NodeIndex>>();
+        deps.sort();
+        deps.dedup();
+        for dep in deps {
+            if cycles.len() >= MAX_CYCLES {
+                return;
+            }
+            if dep == path[0] {
+                cycles.push(path.clone());
+            } else if !path.contains(&dep) {
+                path.push(dep);
+                self.find_cycles(allowed, path, cycles);
+                path.pop();
+            }
         }
     }
+
+    /// Formats the cycle as `a -> b -> a`, followed by where each dependency was declared.
+    fn format_cycle(&self, cycle: &[NodeIndex]) -> String {
+        let mut ids = cycle
+            .iter()
+            .map(|i| self.graph[*i].id.clone())
+            .collect::<Vec<String>>();
+        ids.push(ids[0].clone());
+        let edges = ids
+            .windows(2)
+            .zip(cycle.iter())
+            .map(|(pair, i)| {
+                format!(
+                    "    '{}' depends on '{}' in {}",
+                    pair[0], pair[1], self.graph[*i].executable.location
+                )
+            })
+            .collect::<Vec<String>>();
+        format!("  {}\n{}", ids.join(" -> "), edges.join("\n"))
+    }
 }
 
 impl GraphWorkflow for Workflow {
     fn is_cyclic(&self) -> Result<Vec<&TestNode>> {
-        let topological_order = petgraph::algo::toposort(&self.graph, None);
-        if let Err(err) = topological_order {
-            let node = self.graph.node_weight(err.node_id()).unwrap();
-            Err(anyhow::anyhow!(
-                "The test workflow '{}' has cyclic a dependency!",
-                node.id
-            ))
-        } else {
-            Ok(topological_order
-                .unwrap()
+        match petgraph::algo::toposort(&self.graph, None) {
+            Ok(topological_order) => Ok(topological_order
                 .iter()
                 .map(|i| self.graph.node_weight(*i).unwrap())
-                .collect())
+                .collect()),
+            Err(_) => {
+                let cycles = self.cycles();
+                let mut lines = cycles
+                    .iter()
+                    .map(|cycle| self.format_cycle(cycle))
+                    .collect::<Vec<String>>();
+                if cycles.len() >= MAX_CYCLES {
+                    lines.push(format!(
+                        "  (only the first {} cycles are shown)",
+                        MAX_CYCLES
+                    ));
+                }
+                Err(anyhow::anyhow!(
+                    "The test workflow has cyclic dependencies:\n{}",
+                    lines.join("\n")
+                ))
+            }
         }
     }
 
@@ -59,10 +271,42 @@ impl GraphWorkflow for Workflow {
         orphan_nodes(&self.filter_graph(FilterOptions::all()))
     }
 
+    fn ancestors(&self, node_idx: NodeIndex) -> Vec<&TestNode> {
+        let reversed = Reversed(&self.graph);
+        let mut dfs = Dfs::new(reversed, node_idx);
+        let mut ancestors = Vec::new();
+        while let Some(i) = dfs.next(reversed) {
+            if i != node_idx {
+                ancestors.push(&self.graph[i]);
+            }
+        }
+        ancestors
+    }
+
     fn availables(&self) -> Result<Vec<TestNode>> {
         let graph = self.filter_graph(FilterOptions::not_started());
         let orphans = orphan_nodes(&graph);
-        Ok(orphans.into_iter().map(|n| n.clone()).collect())
+        // The child graph only keeps the NotStarted nodes, so a node that depends on a
+        // running test is also an orphan there, it is only available when all
+        // of its dependencies conditions are satisfied.
+        // A teardown cleans up after a cancelled run as well, so it also accepts cancelled tests.
+        Ok(orphans
+            .into_iter()
+            .filter(|node| {
+                let teardown = node.executable.hook == Some(HookKind::Teardown);
+                self.graph
+                    .edges_directed(
+                        NodeIndex::new(node.index as usize),
+                        petgraph::Direction::Incoming,
+                    )
+                    .all(|edge| {
+                        let status = self.graph[edge.source()].last_status();
+                        (teardown && status == TestStatus::Cancelled)
+                            || edge.weight().is_satisfied(&status).unwrap_or(false)
+                    })
+            })
+            .cloned()
+            .collect())
     }
 
     fn update_node(
@@ -97,33 +341,38 @@ impl GraphWorkflow for Workflow {
     fn update_graph_state(
         &mut self,
         node: TestNode,
-        callback: impl Fn(&TestNode, &str) + Send + Copy + 'static,
+        callback: impl Fn(&TestNode, &str) + Send + Clone + 'static,
     ) {
         // Update node, if the node doesn't exists, do nothing.
-        if !self.update_node(node.clone(), callback) {
+        if !self.update_node(node.clone(), callback.clone()) {
             return;
         }
-        let node_idx = NodeIndex::new(node.index as usize);
-        let status = node.last_status();
-        // update the nodes status that depends on this node
-        match status {
-            TestStatus::Failed | TestStatus::Skipped => {
-                let mut dfs = Dfs::new(&self.graph, node_idx);
-                while let Some(i) = dfs.next(&self.graph) {
-                    // skip the node itself, this is necessary due to the recursive nature of the algorithm
-                    // that can update the node itself many times if it has many dependencies
-                    // setting a wrong status and messing up the history.
-                    if i != node_idx {
-                        self.update_node_status(i, TestStatus::Skipped, callback)
-                    }
+        // update the nodes status that depends on this node,
+        // a dependent whose condition can't be satisfied anymore is skipped,
+        // and so on for its own dependents.
+        let mut finished = vec![NodeIndex::new(node.index as usize)];
+        while let Some(node_idx) = finished.pop() {
+            let status = self.graph[node_idx].last_status();
+            // A cancelled run marks its own pending nodes.
+            if status == TestStatus::Cancelled {
+                continue;
+            }
+            let unsatisfied = self
+                .graph
+                .edges_directed(node_idx, petgraph::Direction::Outgoing)
+                .filter(|edge| edge.weight().is_satisfied(&status) == Some(false))
+                .map(|edge| edge.target())
+                .collect::<Vec<NodeIndex>>();
+            for i in unsatisfied {
+                if self.graph[i].last_status() == TestStatus::NotStarted {
+                    self.update_node_status(i, TestStatus::Skipped, callback.clone());
+                    finished.push(i);
                 }
-                ()
             }
-            _ => (),
-        };
+        }
     }
-    fn filter_graph(&self, filter: FilterOptions) -> DiGraph<&TestNode, &usize> {
-        let graph = self.graph.filter_map(
+    fn filter_graph(&self, filter: FilterOptions) -> DiGraph<&TestNode, &DependencyCondition> {
+        self.graph.filter_map(
             |_node_idx, node| {
                 if filter.check(node) {
                     return Some(node);
@@ -131,27 +380,37 @@ impl GraphWorkflow for Workflow {
                 None
             },
             |_edge_idx, edge| Some(edge),
-        );
-        graph
+        )
     }
     fn as_dot(&self) -> String {
-        format!(
-            "{}",
-            Dot::with_config(&self.graph, &[Config::EdgeIndexLabel])
+        // The setup and teardown hooks are drawn apart from the tests
+        let node_attributes = |_, (_, node): (NodeIndex, &TestNode)| match node.executable.hook {
+            Some(_) => "shape = box style = dashed".to_string(),
+            None => String::new(),
+        };
+        Dot::with_attr_getters(
+            &self.graph,
+            &[Config::EdgeIndexLabel],
+            &|_, _| String::new(),
+            &node_attributes,
         )
+        .to_string()
     }
     fn as_json(&self) -> String {
         let graph = &self.filter_graph(FilterOptions::all());
-        format!("{}", serde_json::to_string(graph).unwrap())
+        serde_json::to_string(graph).unwrap()
     }
     fn reset(&mut self) -> Result<()> {
         // This error can occur if the workflow was created from a graph and not from a manifest.
-        let graph: Result<DiGraph<TestNode, usize>> = self
+        let graph: Result<DiGraph<TestNode, DependencyCondition>> = self
             .manifest
             .clone()
-            .and_then(|x| Some(x.try_into()))
+            .map(|x| x.try_into())
             .ok_or_else(|| anyhow::anyhow!("Cannot reset the workflow without a manifest!"))?;
         self.graph = graph?;
+        if let Some(selection) = self.selection.clone() {
+            self.apply_selection(&selection)?;
+        }
         Ok(())
     }
 }
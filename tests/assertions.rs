@@ -0,0 +1,68 @@
+use thorust::{
+    entities::{
+        enums::{ManifestKind, TestStatus},
+        graph::TestExecutable,
+        manifests::ReqSpec,
+    },
+    services::assertions::final_status,
+};
+
+fn script(command: &str, status: Option<&str>, output: Option<&str>) -> TestExecutable {
+    TestExecutable {
+        id: "foo.test1".to_string(),
+        command: command.to_string(),
+        kind: ManifestKind::Scripts,
+        expect: Some(ReqSpec {
+            status: status.map(|s| s.to_string()),
+            output: output.map(|s| s.to_string()),
+        }),
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn test_expect_matching_status_and_output_completes() {
+    let mut test = script("echo 'test1 foo'", Some("0"), Some("test1 foo"));
+    let call = test.call().await;
+
+    assert_eq!(final_status(&mut test, &call), TestStatus::Completed);
+    assert_eq!(test.reason, None);
+}
+
+#[tokio::test]
+async fn test_expect_mismatch_is_assertion_failed_with_reason() {
+    let mut test = script("echo 'test1 bar'; exit 2", Some("0"), Some("test1 foo"));
+    let call = test.call().await;
+
+    assert_eq!(final_status(&mut test, &call), TestStatus::AssertionFailed);
+    assert_eq!(
+        test.reason.unwrap(),
+        "Assertion failed for 'foo.test1': expected status '0', got '2'; expected output 'test1 foo', got ''"
+    );
+}
+
+#[tokio::test]
+async fn test_expect_status_allows_expected_failures() {
+    let mut test = script("exit 3", Some("3"), None);
+    let call = test.call().await;
+
+    assert!(call.is_err());
+    assert_eq!(final_status(&mut test, &call), TestStatus::Completed);
+}
+
+#[test]
+fn test_expect_grpc_status_by_name_and_output_as_json() {
+    let mut test = TestExecutable {
+        kind: ManifestKind::Grpc,
+        exit_code: Some(5),
+        output: Some("{\n  \"message\": \"not here\"\n}".to_string()),
+        expect: Some(ReqSpec {
+            status: Some("NOT_FOUND".to_string()),
+            output: Some(r#"{"message": "not here"}"#.to_string()),
+        }),
+        ..Default::default()
+    };
+    let call = Ok(test.output.clone().unwrap());
+
+    assert_eq!(final_status(&mut test, &call), TestStatus::Completed);
+}
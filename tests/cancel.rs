@@ -0,0 +1,86 @@
+mod common;
+
+use std::time::{Duration, Instant};
+
+use thorust::{
+    entities::{enums::TestStatus, graph::TestExecutable},
+    runner::cancel::Canceller,
+    traits::RunnerWorkflow,
+};
+
+use common::{is_alive, reason, runner, status, statuses};
+
+#[tokio::test]
+async fn test_canceller_resets_for_each_run() {
+    let canceller = Canceller::default();
+    let token = canceller.token();
+    let shared = canceller.clone();
+    tokio::spawn(async move { shared.cancel() });
+    tokio::time::timeout(Duration::from_secs(1), token.cancelled())
+        .await
+        .unwrap();
+    assert!(canceller.token().is_cancelled());
+    canceller.reset();
+    assert!(!canceller.token().is_cancelled());
+}
+
+#[tokio::test]
+async fn test_dropped_call_kills_its_process_tree() {
+    let pid_file = std::env::temp_dir().join(format!("thorust-cancel-{}", std::process::id()));
+    let mut test = TestExecutable {
+        command: format!("sleep 30 & echo $! > {}; wait", pid_file.display()),
+        ..Default::default()
+    };
+    let canceller = Canceller::default();
+    let token = canceller.token();
+    tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        canceller.cancel();
+    });
+    // the same race used by the runner, the call future is dropped when the run is cancelled
+    tokio::select! {
+        _ = test.call() => panic!("the call should be cancelled"),
+        _ = token.cancelled() => (),
+    };
+
+    let pid = std::fs::read_to_string(&pid_file).unwrap();
+    let _ = std::fs::remove_file(&pid_file);
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(!is_alive(&pid), "the process {} is still alive", pid.trim());
+}
+
+#[tokio::test]
+async fn test_cancel_kills_the_running_tests() {
+    let (mut runner, _) = runner("tests/manifests/sleep.scripts.yaml", Default::default());
+    let canceller = runner.canceller();
+    tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        canceller.cancel();
+    });
+    let start = Instant::now();
+    runner.run_until_complete().await.unwrap();
+    assert!(start.elapsed() < Duration::from_secs(5));
+    assert_eq!(
+        statuses(&runner).await,
+        vec![status("foo.sleep", TestStatus::Cancelled)]
+    );
+}
+
+#[tokio::test]
+async fn test_cancel_before_the_run_applies_to_it() {
+    let (mut runner, _) = runner("tests/manifests/sleep.scripts.yaml", Default::default());
+    runner.canceller().cancel();
+    let start = Instant::now();
+    runner.run_until_complete().await.unwrap();
+    assert!(start.elapsed() < Duration::from_secs(5));
+    assert_eq!(
+        statuses(&runner).await,
+        vec![status("foo.sleep", TestStatus::Cancelled)]
+    );
+    assert_eq!(
+        reason(&runner, "foo.sleep").await.as_deref(),
+        Some("cancelled before it started")
+    );
+    // the next run isn't cancelled anymore
+    assert!(!runner.canceller().token().is_cancelled());
+}
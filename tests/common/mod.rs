@@ -0,0 +1,85 @@
+//! Helpers shared by the integration tests, each test crate only uses some of them.
+#![allow(dead_code)]
+
+use std::{path::PathBuf, sync::Arc};
+
+use thorust::{
+    db::MemoryStorage,
+    entities::enums::TestStatus,
+    parser::parse,
+    runner::{Runner, RunnerOptions},
+    workflow::Workflow,
+};
+
+/// A runner for the manifest, keeping its history in memory.
+pub fn runner(path: &str, options: RunnerOptions) -> (Runner, Arc<MemoryStorage>) {
+    let workflow = Workflow::new(parse(path).unwrap()).unwrap();
+    let storage = Arc::new(MemoryStorage::new());
+    let runner = Runner::with_options(workflow, options, storage.clone()).unwrap();
+    (runner, storage)
+}
+
+/// The last status of every node, in graph order.
+pub async fn statuses(runner: &Runner) -> Vec<(String, TestStatus)> {
+    runner
+        .workflow
+        .read()
+        .await
+        .graph
+        .node_weights()
+        .map(|n| (n.id.clone(), n.last_status()))
+        .collect()
+}
+
+pub fn status(id: &str, status: TestStatus) -> (String, TestStatus) {
+    (id.to_string(), status)
+}
+
+/// The reason of the node's last status.
+pub async fn reason(runner: &Runner, id: &str) -> Option<String> {
+    let workflow = runner.workflow.read().await;
+    workflow.find_node(id).unwrap().executable.reason.clone()
+}
+
+/// Checks the process state in `/proc`,
+/// a killed process may be kept as a zombie until its parent reaps it.
+pub fn is_alive(pid: &str) -> bool {
+    let stat = std::fs::read_to_string(format!("/proc/{}/stat", pid.trim())).unwrap_or_default();
+    match stat.rsplit_once(')') {
+        Some((_, state)) => !state.trim_start().starts_with('Z'),
+        None => false,
+    }
+}
+
+/// The tables of the history as they were created before the runs were kept.
+pub const LEGACY_SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS nodes (
+    id              INTEGER PRIMARY KEY,
+    name            TEXT NOT NULL,
+    description     TEXT NOT NULL,
+    service         TEXT NOT NULL,
+    test_id         TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS node_history (
+    id              INTEGER PRIMARY KEY,
+    status          TEXT NOT NULL,
+    node            INTEGER NOT NULL,
+    created_at      TIMESTAMP DEFAULT(STRFTIME('%Y-%m-%d %H:%M:%f', 'NOW')),
+    data            TEXT DEFAULT '',
+    FOREIGN KEY(node) REFERENCES nodes(id)
+);
+CREATE TABLE IF NOT EXISTS graph (
+    id              INTEGER PRIMARY KEY,
+    dot             TEXT NOT NULL,
+    created_at      TIMESTAMP DEFAULT(STRFTIME('%Y-%m-%d %H:%M:%f', 'NOW'))
+);
+INSERT INTO nodes (id, name, description, service, test_id) VALUES (0, 'one', 'old', 'bar', 'bar.one');
+INSERT INTO node_history (status, node) VALUES ('Completed', 0);
+INSERT INTO graph (dot) VALUES ('digraph {}');";
+
+/// A database path in the temp dir, unique for the test process, removed if it exists.
+pub fn db_path(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("thorust-{}-{}.db", name, std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    path
+}
@@ -0,0 +1,113 @@
+mod common;
+
+use thorust::{
+    entities::{enums::TestStatus, graph::TestExecutable},
+    parser::parse,
+    runner::admission::can_start,
+    traits::{Manifest, RunnerWorkflow, Storage},
+};
+
+use common::{runner, status, statuses};
+
+fn executable(service: &str, locks: &[&str]) -> TestExecutable {
+    TestExecutable {
+        service: service.to_string(),
+        locks: locks.iter().map(|l| l.to_string()).collect(),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_concurrency_options_from_manifest() {
+    let manifest = parse("tests/manifests/concurrency.scripts.yaml").unwrap();
+    let nodes = manifest.as_test_nodes().unwrap();
+    let options: Vec<(Option<usize>, Vec<String>, bool)> = nodes
+        .iter()
+        .map(|n| {
+            (
+                n.executable.max_parallel,
+                n.executable.locks.clone(),
+                n.executable.exclusive,
+            )
+        })
+        .collect();
+    assert_eq!(
+        options,
+        vec![
+            (Some(1), vec!["database".to_string()], false),
+            (Some(1), vec!["database".to_string()], false),
+            (None, vec![], true),
+        ]
+    );
+}
+
+#[test]
+fn test_can_start_with_jobs_and_max_parallel() {
+    let foo = executable("foo", &[]);
+    let bar = executable("bar", &[]);
+    assert!(can_start(&foo, &[], Some(0)));
+    assert!(can_start(&foo, &[&bar], Some(2)));
+    assert!(!can_start(&foo, &[&bar, &bar], Some(2)));
+    assert!(can_start(&foo, &[&bar, &bar], None));
+
+    let limited = TestExecutable {
+        max_parallel: Some(1),
+        ..executable("foo", &[])
+    };
+    assert!(!can_start(&limited, &[&foo], None));
+    assert!(can_start(&limited, &[&bar], None));
+}
+
+#[test]
+fn test_can_start_with_locks_and_exclusive() {
+    let db = executable("foo", &["database"]);
+    let cache = executable("bar", &["cache"]);
+    assert!(!can_start(
+        &db,
+        &[&executable("bar", &["cache", "database"])],
+        None
+    ));
+    assert!(can_start(&db, &[&cache], None));
+
+    let exclusive = TestExecutable {
+        exclusive: true,
+        ..executable("baz", &[])
+    };
+    assert!(can_start(&exclusive, &[], None));
+    assert!(!can_start(&exclusive, &[&cache], None));
+    assert!(!can_start(&cache, &[&exclusive], None));
+}
+
+#[tokio::test]
+async fn test_tests_sharing_a_lock_never_overlap() {
+    let (mut runner, storage) = runner("tests/manifests/locks.scripts.yaml", Default::default());
+    runner.run_until_complete().await.unwrap();
+    assert_eq!(
+        statuses(&runner).await,
+        vec![
+            status("foo.one", TestStatus::Completed),
+            status("bar.two", TestStatus::Completed),
+        ]
+    );
+    // when each test was running, as (Running, Completed) timestamps
+    let mut spans = (0..2)
+        .map(|node| {
+            let history = storage.get_node_history(runner.run_id(), node).unwrap();
+            let at = |status: &str| {
+                history
+                    .iter()
+                    .find(|h| h.status == status)
+                    .unwrap()
+                    .created_at
+                    .clone()
+            };
+            (at("Running"), at("Completed"))
+        })
+        .collect::<Vec<(String, String)>>();
+    spans.sort();
+    assert!(
+        spans[0].1 <= spans[1].0,
+        "the tests have overlapped: {:?}",
+        spans
+    );
+}
@@ -0,0 +1,198 @@
+mod common;
+
+use std::time::{Duration, Instant};
+
+use thorust::{
+    entities::{
+        enums::TestStatus,
+        graph::TestExecutable,
+        manifests::ReadyProbe,
+        validation::{ValidationErrors, ValidationProblem},
+    },
+    parser::parse,
+    runner::Runner,
+    services::daemons::{start_daemon, Daemons},
+    traits::{GraphWorkflow, Manifest, RunnerWorkflow},
+};
+use tokio::{io::AsyncWriteExt, net::TcpListener};
+
+use common::{is_alive, runner, status, statuses};
+
+fn daemon(command: &str, ready: ReadyProbe) -> TestExecutable {
+    TestExecutable {
+        id: "api.server".to_string(),
+        name: "server".to_string(),
+        command: command.to_string(),
+        timeout: Some(Duration::from_secs(5)),
+        daemon: Some(ready),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_daemons_as_test_nodes() {
+    let manifest = parse("tests/manifests/daemons.scripts.yaml").unwrap();
+    let nodes = manifest.as_test_nodes().unwrap();
+    let ids = nodes.iter().map(|n| n.id.as_str()).collect::<Vec<&str>>();
+    assert_eq!(
+        ids,
+        vec![
+            "api.setup",
+            "api.server",
+            "api.health",
+            "web.frontend",
+            "web.index"
+        ]
+    );
+    assert_eq!(
+        nodes[1].executable.daemon,
+        Some(ReadyProbe::Log("Listening on".to_string()))
+    );
+    assert_eq!(nodes[1].executable.timeout, Some(Duration::from_secs(10)));
+    assert_eq!(nodes[1].depends_on, vec!["api.setup"]);
+    assert_eq!(nodes[1].executable.location.line, 8);
+    assert_eq!(nodes[3].executable.timeout, Some(Duration::from_secs(2)));
+    assert_eq!(nodes[3].executable.location.line, 22);
+    assert_eq!(nodes[4].depends_on, vec!["api.server", "web.frontend"]);
+}
+
+#[test]
+fn test_invalid_daemons_probes() {
+    let err = parse("tests/manifests/invalid_daemons.scripts.yaml").unwrap_err();
+    let errors = err.downcast_ref::<ValidationErrors>().unwrap();
+    let problems: Vec<String> = errors
+        .problems
+        .iter()
+        .map(ValidationProblem::to_string)
+        .collect();
+    assert_eq!(problems.len(), 2);
+    assert!(problems[0].starts_with(
+        "tests/manifests/invalid_daemons.scripts.yaml:5:9: The daemon 'api.server' has an invalid ready log regex: "
+    ));
+    assert_eq!(
+        problems[1],
+        "tests/manifests/invalid_daemons.scripts.yaml:10:9: The daemon 'api.secure' has an invalid ready url 'https://localhost:8443', only http:// is supported"
+    );
+}
+
+#[tokio::test]
+async fn test_daemon_ready_on_log_line_until_stopped() {
+    let mut test = daemon(
+        "echo $$; echo starting; sleep 0.2; echo Listening on 8080; sleep 30",
+        ReadyProbe::Log("Listening on \\d+".to_string()),
+    );
+    let daemons = Daemons::default();
+    let output = daemons.start(&mut test).await.unwrap();
+    let lines = output.lines().collect::<Vec<&str>>();
+    assert_eq!(lines[1..], ["starting", "Listening on 8080"]);
+    assert!(is_alive(lines[0]));
+
+    daemons.stop();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(
+        !is_alive(lines[0]),
+        "the daemon {} is still alive",
+        lines[0]
+    );
+}
+
+#[tokio::test]
+async fn test_daemon_ready_on_tcp_and_http() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        while let Ok((mut stream, _)) = listener.accept().await {
+            let _ = stream.write_all(b"HTTP/1.1 200 OK\r\n\r\n").await;
+        }
+    });
+    let mut test = daemon("sleep 30", ReadyProbe::Tcp(address.to_string()));
+    assert!(start_daemon(&mut test).await.is_ok());
+    let url = format!("http://{}/health", address);
+    let mut test = daemon("sleep 30", ReadyProbe::Http(url));
+    assert!(start_daemon(&mut test).await.is_ok());
+}
+
+#[tokio::test]
+async fn test_daemon_not_ready() {
+    let mut test = daemon("echo failing; exit 3", ReadyProbe::Log("ready".to_string()));
+    assert!(start_daemon(&mut test).await.is_err());
+    assert_eq!(test.exit_code, Some(3));
+    assert_eq!(test.output, Some("failing".to_string()));
+
+    // nothing listens on the port, the daemon times out and is killed
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    drop(listener);
+    let mut test = daemon("echo $$; sleep 30", ReadyProbe::Tcp(address));
+    test.timeout = Some(Duration::from_millis(300));
+    let start = Instant::now();
+    assert!(start_daemon(&mut test).await.is_err());
+    assert!(start.elapsed() < Duration::from_secs(5));
+    assert_eq!(test.reason, Some("timed out after 300ms".to_string()));
+    let pid = test.output.unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(!is_alive(&pid), "the daemon {} is still alive", pid);
+}
+
+#[tokio::test]
+async fn test_tests_run_while_their_daemons_are_up() {
+    let (mut runner, _) = runner("tests/manifests/daemons.scripts.yaml", Default::default());
+    runner.run_until_complete().await.unwrap();
+    // the frontend never gets ready, nothing listens on its port
+    assert_eq!(
+        statuses(&runner).await,
+        vec![
+            status("api.setup", TestStatus::Completed),
+            status("api.server", TestStatus::Completed),
+            status("api.health", TestStatus::Completed),
+            status("web.frontend", TestStatus::Failed),
+            status("web.index", TestStatus::Skipped),
+        ]
+    );
+    // the daemons are killed once the run has ended
+    let pid = server_pid(&runner).await;
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(!is_alive(&pid), "the daemon {} is still alive", pid);
+}
+
+#[tokio::test]
+async fn test_daemons_outlive_the_waves_of_a_run() {
+    let (mut runner, _) = runner("tests/manifests/daemons.scripts.yaml", Default::default());
+    // as the UI does, through `/api/runner/batch`: the server starts in a wave, its health later
+    let mut waves = 0;
+    loop {
+        let availables = runner.workflow.read().await.availables().unwrap();
+        if availables.is_empty() {
+            break;
+        }
+        runner.batch_execute(availables).await.unwrap();
+        waves += 1;
+    }
+    assert_eq!(waves, 3);
+    assert_eq!(
+        statuses(&runner).await,
+        vec![
+            status("api.setup", TestStatus::Completed),
+            status("api.server", TestStatus::Completed),
+            status("api.health", TestStatus::Completed),
+            status("web.frontend", TestStatus::Failed),
+            status("web.index", TestStatus::Skipped),
+        ]
+    );
+    // the daemons are killed once there is nothing left to run
+    let pid = server_pid(&runner).await;
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(!is_alive(&pid), "the daemon {} is still alive", pid);
+}
+
+/// The pid of the api server, printed once it is ready.
+async fn server_pid(runner: &Runner) -> String {
+    let workflow = runner.workflow.read().await;
+    let output = workflow
+        .find_node("api.server")
+        .unwrap()
+        .executable
+        .output
+        .clone();
+    output.unwrap().rsplit(' ').next().unwrap().to_string()
+}
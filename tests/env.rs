@@ -0,0 +1,55 @@
+use std::path::PathBuf;
+
+use thorust::{entities::conversions::new_uuidv4, parser::parse};
+
+/// Creates a temporary manifest directory with a `.env` file
+fn manifest_dir(manifest: &str, dotenv: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("thorust-{}", new_uuidv4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("greeter.grpc.yaml"), manifest).unwrap();
+    std::fs::write(dir.join(".env"), dotenv).unwrap();
+    dir
+}
+
+const MANIFEST: &str = r#"
+type: grpc
+services:
+  - name: greeter
+    address: ${THORUST_ENV_TEST_HOST}:${THORUST_ENV_TEST_PORT:-50051}
+    tests:
+      - name: say hello
+        id: hello
+        description: Says hello to ${THORUST_ENV_TEST_NAME}
+        method: helloworld.Greeter/SayHello
+        proto: tests/protos/helloworld.proto
+        body: '{"name": "${THORUST_ENV_TEST_NAME}"}'
+"#;
+
+#[test]
+fn test_parse_expands_env_vars_from_dotenv() {
+    let dir = manifest_dir(
+        MANIFEST,
+        "THORUST_ENV_TEST_HOST=localhost\nTHORUST_ENV_TEST_NAME=thorust\n",
+    );
+    let manifest = parse(dir.join("greeter.grpc.yaml").to_str().unwrap()).unwrap();
+    let service = &manifest.grpc.unwrap().services[0];
+
+    assert_eq!(service.address, "localhost:50051");
+    assert_eq!(service.tests[0].body, r#"{"name": "thorust"}"#);
+    assert_eq!(service.tests[0].description, "Says hello to thorust");
+}
+
+#[test]
+fn test_parse_with_missing_env_var_fails() {
+    let dir = manifest_dir(MANIFEST, "THORUST_ENV_TEST_HOST=localhost\n");
+    let fp = dir.join("greeter.grpc.yaml");
+    let err = parse(fp.to_str().unwrap()).unwrap_err();
+
+    assert_eq!(
+        err.to_string(),
+        format!(
+            "Missing environment variable 'THORUST_ENV_TEST_NAME' required by the field 'services[0].tests[0].body' in file '{}'",
+            fp.display()
+        )
+    );
+}
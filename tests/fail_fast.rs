@@ -0,0 +1,97 @@
+mod common;
+
+use std::time::{Duration, Instant};
+
+use thorust::{
+    entities::enums::TestStatus,
+    runner::RunnerOptions,
+    traits::{RunnerWorkflow, Storage},
+};
+
+use common::{reason, runner, status, statuses};
+
+#[tokio::test]
+async fn test_fail_fast_cancels_the_pending_tests() {
+    let options = RunnerOptions {
+        jobs: Some(1),
+        fail_fast: true,
+        ..Default::default()
+    };
+    let (mut runner, storage) = runner("tests/manifests/runner.scripts.yaml", options);
+    runner.run_until_complete().await.unwrap();
+    assert_eq!(
+        statuses(&runner).await,
+        vec![
+            status("foo.fails", TestStatus::Failed),
+            status("foo.after", TestStatus::Skipped),
+            status("bar.one", TestStatus::Cancelled),
+            status("bar.two", TestStatus::Cancelled),
+        ]
+    );
+    let run = storage.get_last_run().unwrap().unwrap();
+    assert_eq!((run.failed, run.cancelled), (1, 2));
+    assert_eq!(
+        reason(&runner, "bar.one").await.as_deref(),
+        Some("not started, fail-fast after 'foo.fails' has Failed")
+    );
+}
+
+#[tokio::test]
+async fn test_fail_fast_with_cancel_running_kills_the_running_tests() {
+    let options = RunnerOptions {
+        fail_fast: true,
+        cancel_running: true,
+        ..Default::default()
+    };
+    let (mut runner, storage) = runner("tests/manifests/fail_fast.scripts.yaml", options);
+    let start = Instant::now();
+    runner.run_until_complete().await.unwrap();
+    assert!(start.elapsed() < Duration::from_secs(5));
+    assert_eq!(
+        statuses(&runner).await,
+        vec![
+            status("foo.fails", TestStatus::Failed),
+            status("bar.sleep", TestStatus::Cancelled),
+        ]
+    );
+    assert_eq!(
+        reason(&runner, "bar.sleep").await.as_deref(),
+        Some("cancelled while running")
+    );
+    let run = storage.get_last_run().unwrap().unwrap();
+    assert_eq!((run.failed, run.cancelled), (1, 1));
+}
+
+#[tokio::test]
+async fn test_fail_fast_lets_the_spawned_tests_run() {
+    let options = RunnerOptions {
+        fail_fast: true,
+        ..Default::default()
+    };
+    let (mut runner, storage) = runner("tests/manifests/fail_fast_race.scripts.yaml", options);
+    let run = tokio::spawn(async move {
+        runner.run_until_complete().await.unwrap();
+        runner
+    });
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    // Blocks the runtime until both tests have exited, so they finish together:
+    // `bar.after` is spawned right before the fail-fast stop, but its task hasn't run yet.
+    std::thread::sleep(Duration::from_millis(800));
+    let runner = run.await.unwrap();
+    assert_eq!(
+        statuses(&runner).await,
+        vec![
+            status("foo.fails", TestStatus::Failed),
+            status("bar.completes", TestStatus::Completed),
+            status("bar.after", TestStatus::Completed),
+        ]
+    );
+    let history = storage.get_node_history(runner.run_id(), 2).unwrap();
+    assert_eq!(
+        history
+            .iter()
+            .map(|h| h.status.as_str())
+            .collect::<Vec<&str>>(),
+        vec!["NotStarted", "Running", "Completed"]
+    );
+}
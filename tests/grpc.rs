@@ -0,0 +1,156 @@
+use std::{convert::Infallible, net::SocketAddr};
+
+use prost_reflect::{DynamicMessage, MethodDescriptor, Value};
+use tonic::{
+    body::BoxBody,
+    codegen::{empty_body, http, Body, BoxFuture, Context, Poll, Service, StdError},
+    server::NamedService,
+    transport::{server::TcpIncoming, Server},
+    Request, Response, Status,
+};
+
+use thorust::{
+    entities::{
+        enums::ManifestKind,
+        graph::{GrpcRequest, TestExecutable},
+    },
+    services::grpc_client::{load_method, DynamicCodec},
+};
+
+const PROTO: &str = "tests/protos/helloworld.proto";
+
+/// `helloworld.Greeter` implementation built on top of dynamic messages,
+/// the greeting prefix can be changed through the `x-greeting` metadata.
+#[derive(Clone)]
+struct Greeter {
+    method: MethodDescriptor,
+}
+
+impl NamedService for Greeter {
+    const NAME: &'static str = "helloworld.Greeter";
+}
+
+#[derive(Clone)]
+struct SayHello {
+    method: MethodDescriptor,
+}
+
+impl Service<Request<DynamicMessage>> for SayHello {
+    type Response = Response<DynamicMessage>;
+    type Error = Status;
+    type Future = BoxFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: Request<DynamicMessage>) -> Self::Future {
+        let output = self.method.output();
+        Box::pin(async move {
+            let greeting = request
+                .metadata()
+                .get("x-greeting")
+                .and_then(|v| v.to_str().ok())
+                .unwrap_or("Hello")
+                .to_string();
+            let name = request
+                .get_ref()
+                .get_field_by_name("name")
+                .and_then(|v| v.as_str().map(|s| s.to_string()))
+                .unwrap_or_default();
+            if name.is_empty() {
+                return Err(Status::invalid_argument("name is required"));
+            }
+            let mut reply = DynamicMessage::new(output);
+            reply.set_field_by_name("message", Value::String(format!("{} {}", greeting, name)));
+            Ok(Response::new(reply))
+        })
+    }
+}
+
+impl<B> Service<http::Request<B>> for Greeter
+where
+    B: Body + Send + 'static,
+    B::Error: Into<StdError> + Send + 'static,
+{
+    type Response = http::Response<BoxBody>;
+    type Error = Infallible;
+    type Future = BoxFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: http::Request<B>) -> Self::Future {
+        let method = self.method.clone();
+        Box::pin(async move {
+            if req.uri().path() != "/helloworld.Greeter/SayHello" {
+                return Ok(http::Response::builder()
+                    .status(200)
+                    .header("grpc-status", "12")
+                    .header("content-type", "application/grpc")
+                    .body(empty_body())
+                    .unwrap());
+            }
+            let mut grpc = tonic::server::Grpc::new(DynamicCodec::new(method.input()));
+            Ok(grpc.unary(SayHello { method }, req).await)
+        })
+    }
+}
+
+async fn start_server() -> SocketAddr {
+    let method = load_method(PROTO, "helloworld.Greeter/SayHello").unwrap();
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let incoming = TcpIncoming::from_listener(listener, true, None).unwrap();
+    tokio::spawn(
+        Server::builder()
+            .add_service(Greeter { method })
+            .serve_with_incoming(incoming),
+    );
+    addr
+}
+
+fn grpc_executable(addr: SocketAddr, body: &str, headers: Vec<String>) -> TestExecutable {
+    TestExecutable {
+        name: "say hello".to_string(),
+        kind: ManifestKind::Grpc,
+        grpc: Some(GrpcRequest {
+            address: addr.to_string(),
+            proto: PROTO.to_string(),
+            method: "helloworld.Greeter/SayHello".to_string(),
+            body: body.to_string(),
+            headers,
+        }),
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn test_grpc_call_sends_body_and_headers() {
+    let addr = start_server().await;
+    let mut executable = grpc_executable(
+        addr,
+        r#"{"name": "thorust"}"#,
+        vec!["x-greeting: Hi".to_string()],
+    );
+
+    let output = executable.call().await.unwrap();
+    let response: serde_json::Value = serde_json::from_str(&output).unwrap();
+
+    assert_eq!(response["message"], "Hi thorust");
+    assert_eq!(executable.exit_code, Some(0));
+}
+
+#[tokio::test]
+async fn test_grpc_call_with_error_status_fails() {
+    let addr = start_server().await;
+    let mut executable = grpc_executable(addr, r#"{"name": ""}"#, vec![]);
+
+    assert!(executable.call().await.is_err());
+    assert_eq!(
+        executable.exit_code,
+        Some(tonic::Code::InvalidArgument as i32)
+    );
+    assert_eq!(executable.output, Some("name is required".to_string()));
+}
@@ -0,0 +1,187 @@
+mod common;
+
+use thorust::{
+    entities::enums::{DependencyCondition, HookKind, TestStatus},
+    parser::parse,
+    runner::RunnerOptions,
+    traits::{GraphWorkflow, Manifest, RunnerWorkflow},
+    workflow::Workflow,
+};
+
+use common::{runner, status, statuses};
+
+fn ids(nodes: &[thorust::entities::graph::TestNode]) -> Vec<String> {
+    nodes.iter().map(|n| n.id.clone()).collect()
+}
+
+fn complete(workflow: &mut Workflow, id: &str, status: TestStatus) {
+    let mut node = workflow
+        .availables()
+        .unwrap()
+        .into_iter()
+        .find(|n| n.id == id)
+        .unwrap();
+    node.status.push(status);
+    workflow.update_graph_state(node, |_, _| {});
+}
+
+#[test]
+fn test_hooks_as_test_nodes() {
+    let manifest = parse("tests/manifests/hooks.scripts.yaml").unwrap();
+    let nodes = manifest.as_test_nodes().unwrap();
+    assert_eq!(
+        ids(&nodes),
+        vec![
+            "setup",
+            "foo.setup",
+            "foo.first",
+            "foo.second",
+            "foo.teardown",
+            "bar.first",
+            "teardown"
+        ]
+    );
+    let hooks = nodes
+        .iter()
+        .map(|n| n.executable.hook)
+        .collect::<Vec<Option<HookKind>>>();
+    assert_eq!(
+        hooks,
+        vec![
+            Some(HookKind::Setup),
+            Some(HookKind::Setup),
+            None,
+            None,
+            Some(HookKind::Teardown),
+            None,
+            Some(HookKind::Teardown)
+        ]
+    );
+    assert_eq!(nodes[0].executable.description, "migrates the database");
+    assert_eq!(
+        nodes[4].executable.description,
+        "The teardown of the service 'foo'"
+    );
+    assert_eq!(nodes[3].depends_on, vec!["foo.first", "foo.setup", "setup"]);
+    assert_eq!(
+        nodes[4].depends_on,
+        vec!["foo.setup", "foo.first", "foo.second", "setup"]
+    );
+    assert_eq!(
+        nodes[4].executable.conditions.get("foo.second"),
+        Some(&DependencyCondition::Always)
+    );
+    assert_eq!(nodes[6].depends_on.len(), 6);
+    assert_eq!(nodes[1].executable.location.line, 9);
+}
+
+#[test]
+fn test_failed_setup_skips_the_service_tests() {
+    let manifest = parse("tests/manifests/hooks.scripts.yaml").unwrap();
+    let mut workflow = Workflow::new(manifest).unwrap();
+    assert_eq!(ids(&workflow.availables().unwrap()), vec!["setup"]);
+    complete(&mut workflow, "setup", TestStatus::Completed);
+    assert_eq!(
+        ids(&workflow.availables().unwrap()),
+        vec!["foo.setup", "bar.first"]
+    );
+    complete(&mut workflow, "foo.setup", TestStatus::Failed);
+    let statuses = workflow
+        .graph
+        .node_weights()
+        .map(|n| n.last_status())
+        .collect::<Vec<TestStatus>>();
+    assert_eq!(
+        statuses[1..4],
+        [TestStatus::Failed, TestStatus::Skipped, TestStatus::Skipped]
+    );
+    // The teardown runs anyway, the run teardown waits for bar.first
+    assert_eq!(
+        ids(&workflow.availables().unwrap()),
+        vec!["foo.teardown", "bar.first"]
+    );
+    complete(&mut workflow, "foo.teardown", TestStatus::Completed);
+    complete(&mut workflow, "bar.first", TestStatus::Failed);
+    assert_eq!(ids(&workflow.availables().unwrap()), vec!["teardown"]);
+}
+
+#[test]
+fn test_teardowns_run_after_cancelled_tests() {
+    let manifest = parse("tests/manifests/hooks.scripts.yaml").unwrap();
+    let mut workflow = Workflow::new(manifest).unwrap();
+    complete(&mut workflow, "setup", TestStatus::Completed);
+    // as a cancelled run leaves them
+    for id in ["foo.setup", "foo.first", "foo.second", "bar.first"] {
+        let mut node = workflow.find_node(id).unwrap().clone();
+        node.status.push(TestStatus::Cancelled);
+        workflow.update_graph_state(node, |_, _| {});
+    }
+    assert_eq!(ids(&workflow.availables().unwrap()), vec!["foo.teardown"]);
+    complete(&mut workflow, "foo.teardown", TestStatus::Completed);
+    assert_eq!(ids(&workflow.availables().unwrap()), vec!["teardown"]);
+}
+
+#[tokio::test]
+async fn test_teardowns_run_after_a_fail_fast_stop() {
+    let options = RunnerOptions {
+        jobs: Some(1),
+        fail_fast: true,
+        ..Default::default()
+    };
+    let (mut runner, _) = runner("tests/manifests/hooks.scripts.yaml", options);
+    runner.run_until_complete().await.unwrap();
+    assert_eq!(
+        statuses(&runner).await,
+        vec![
+            status("setup", TestStatus::Completed),
+            status("foo.setup", TestStatus::Failed),
+            status("foo.first", TestStatus::Skipped),
+            status("foo.second", TestStatus::Skipped),
+            status("foo.teardown", TestStatus::Completed),
+            status("bar.first", TestStatus::Cancelled),
+            status("teardown", TestStatus::Completed),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_teardowns_run_after_a_cancel() {
+    let (mut runner, _) = runner(
+        "tests/manifests/cancel_teardown.scripts.yaml",
+        Default::default(),
+    );
+    let canceller = runner.canceller();
+    tokio::spawn(async move {
+        tokio::time::sleep(std::time::Duration::from_millis(300)).await;
+        canceller.cancel();
+    });
+    runner.run_until_complete().await.unwrap();
+    assert_eq!(
+        statuses(&runner).await,
+        vec![
+            status("foo.sleep", TestStatus::Cancelled),
+            status("foo.after", TestStatus::Cancelled),
+            status("foo.teardown", TestStatus::Completed),
+        ]
+    );
+}
+
+#[test]
+fn test_hooks_in_dot() {
+    let manifest = parse("tests/manifests/hooks.scripts.yaml").unwrap();
+    let workflow = Workflow::new(manifest).unwrap();
+    let dot = workflow.as_dot();
+    assert!(dot.contains(r#"1 [ label = "foo.setup-NotStarted" shape = box style = dashed]"#));
+    assert!(dot.contains(r#"2 [ label = "foo.first-NotStarted" ]"#));
+}
+
+#[test]
+fn test_run_hooks_declared_twice() {
+    let err = parse("tests/manifests/hooks").unwrap_err();
+    // the manifests of a directory are read in order of path
+    assert_eq!(
+        err.to_string(),
+        "The run setup is declared in more than one manifest: \
+         tests/manifests/hooks/first.scripts.yaml, tests/manifests/hooks/second.scripts.yaml"
+    );
+}
@@ -0,0 +1,27 @@
+use thorust::lint::lint;
+
+#[test]
+fn test_lint_warnings() {
+    let report = lint("tests/manifests/lint.scripts.yaml");
+    assert!(report.is_ok());
+    let warnings: Vec<String> = report.warnings.iter().map(|w| w.to_string()).collect();
+    assert_eq!(
+        warnings,
+        vec![
+            "tests/manifests/lint.scripts.yaml:22:5: The service 'unused' has no tests",
+            "tests/manifests/lint.scripts.yaml:5:9: The test id 'foo.first' has no description",
+            "tests/manifests/lint.scripts.yaml:15:9: The test id 'foo.third' has a redundant dependency on 'foo.first', it is already required by 'foo.second'",
+        ]
+    );
+}
+
+#[test]
+fn test_lint_errors() {
+    let report = lint("tests/manifests/invalid");
+    assert!(!report.is_ok());
+    assert_eq!(report.errors.len(), 8);
+
+    let report = lint("tests/manifests/cyclic.scripts.yaml");
+    assert!(!report.is_ok());
+    assert!(report.errors[0].message.contains("cyclic"));
+}
@@ -0,0 +1,135 @@
+mod common;
+
+use thorust::{
+    db::{migrations::MIGRATIONS, SqliteStorage},
+    entities::storage::{DbNode, StorageError},
+    traits::Storage,
+};
+
+use common::{db_path, LEGACY_SCHEMA};
+
+fn latest() -> u32 {
+    MIGRATIONS.last().unwrap().version
+}
+
+#[test]
+fn test_migrations_are_applied_once() {
+    let path = db_path("migrate");
+    let storage = SqliteStorage::connect(&path).unwrap();
+    let info = storage.schema_info().unwrap();
+    assert_eq!(info.version, 0);
+    assert!(info.migrations.iter().all(|m| m.applied_at.is_none()));
+
+    let versions = MIGRATIONS.iter().map(|m| m.version).collect::<Vec<u32>>();
+    assert_eq!(storage.migrate().unwrap(), versions);
+    assert!(storage.migrate().unwrap().is_empty());
+    let info = storage.schema_info().unwrap();
+    assert_eq!((info.version, info.latest), (latest(), latest()));
+    assert!(info.migrations.iter().all(|m| m.applied_at.is_some()));
+
+    // opening the storage again doesn't apply anything
+    let storage = SqliteStorage::open(&path).unwrap();
+    assert_eq!(
+        storage.schema_info().unwrap().migrations.len(),
+        versions.len()
+    );
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn test_baseline_database_is_migrated() {
+    let path = db_path("baseline");
+    // the tables as they were created before the runs and the migrations
+    rusqlite::Connection::open(&path)
+        .unwrap()
+        .execute_batch(LEGACY_SCHEMA)
+        .unwrap();
+
+    // as `db migrate` does it
+    let storage = SqliteStorage::connect(&path).unwrap();
+    let versions = MIGRATIONS.iter().map(|m| m.version).collect::<Vec<u32>>();
+    assert_eq!(storage.migrate().unwrap(), versions);
+    assert_eq!(storage.schema_info().unwrap().version, latest());
+
+    // the old rows are kept apart
+    let count = |table: &str| -> i64 {
+        storage
+            .conn()
+            .query_row(&format!("SELECT COUNT(*) FROM {}", table), [], |row| {
+                row.get(0)
+            })
+            .unwrap()
+    };
+    assert_eq!(count("nodes_legacy"), 1);
+    assert_eq!(count("node_history_legacy"), 1);
+    assert_eq!(count("graph_legacy"), 1);
+
+    // and the new tables are scoped by run
+    let run = storage.insert_run("new.scripts.yaml").unwrap();
+    let node = DbNode {
+        id: 0,
+        name: "one".to_string(),
+        test_id: "bar.one".to_string(),
+        description: "new".to_string(),
+        service: "bar".to_string(),
+    };
+    storage.insert_node(run, node).unwrap();
+    storage
+        .insert_node_history(run, "Completed", 0, "")
+        .unwrap();
+    storage.insert_dot(run, "digraph {}").unwrap();
+    assert_eq!(storage.get_all_nodes(run).unwrap()[0].description, "new");
+    assert_eq!(storage.get_node_history(run, 0).unwrap().len(), 1);
+    assert_eq!(storage.get_dots(run).unwrap().len(), 1);
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn test_database_without_migrations_keeps_its_runs() {
+    let path = db_path("unversioned");
+    // the tables as they were created before the migrations
+    let conn = rusqlite::Connection::open(&path).unwrap();
+    conn.execute_batch(
+        "CREATE TABLE runs (
+            id                  INTEGER PRIMARY KEY,
+            manifest            TEXT NOT NULL,
+            started_at          TIMESTAMP DEFAULT(STRFTIME('%Y-%m-%d %H:%M:%f', 'NOW')),
+            finished_at         TIMESTAMP,
+            completed           INTEGER NOT NULL DEFAULT 0,
+            failed              INTEGER NOT NULL DEFAULT 0,
+            assertion_failed    INTEGER NOT NULL DEFAULT 0,
+            skipped             INTEGER NOT NULL DEFAULT 0,
+            cancelled           INTEGER NOT NULL DEFAULT 0,
+            total               INTEGER NOT NULL DEFAULT 0
+        );
+        INSERT INTO runs (manifest) VALUES ('old.scripts.yaml');",
+    )
+    .unwrap();
+    drop(conn);
+
+    let storage = SqliteStorage::open(&path).unwrap();
+    assert_eq!(storage.schema_info().unwrap().version, latest());
+    let runs = storage.get_runs().unwrap();
+    assert_eq!(runs.len(), 1);
+    assert_eq!(runs[0].manifest, "old.scripts.yaml");
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn test_newer_database_is_refused() {
+    let path = db_path("newer");
+    let storage = SqliteStorage::open(&path).unwrap();
+    storage
+        .conn()
+        .execute(
+            "INSERT INTO schema_migrations (version, description) VALUES (?1, 'from the future')",
+            [latest() + 1],
+        )
+        .unwrap();
+    drop(storage);
+    match SqliteStorage::open(&path) {
+        Err(StorageError::Database(message)) => assert!(message.contains("is newer than")),
+        other => panic!("unexpected result: {:?}", other.map(|_| ())),
+    }
+    let _ = std::fs::remove_file(&path);
+}
@@ -0,0 +1,44 @@
+use std::collections::HashSet;
+
+use petgraph::{stable_graph::NodeIndex, Direction};
+use thorust::{
+    entities::{enums::ManifestKind, storage::DbNode},
+    parser::parse,
+    workflow::Workflow,
+};
+
+#[test]
+fn test_mixed_manifest_kinds_build_one_graph_with_unique_indexes() {
+    let manifest = parse("tests/manifests/mixed").unwrap();
+    let workflow = Workflow::new(manifest).unwrap();
+    let graph = &workflow.graph;
+
+    assert_eq!(graph.node_count(), 4);
+    // the node index must match its position in the graph, otherwise the edges point to wrong nodes
+    for idx in graph.node_indices() {
+        assert_eq!(graph[idx].index as usize, idx.index());
+    }
+    let db_ids = graph
+        .node_weights()
+        .map(|n| DbNode::from(n.clone()).id)
+        .collect::<HashSet<i32>>();
+    assert_eq!(db_ids.len(), 4);
+
+    let find = |id: &str| graph.node_indices().find(|i| graph[*i].id == id).unwrap();
+    let parents = |idx: NodeIndex| {
+        let mut ids = graph
+            .neighbors_directed(idx, Direction::Incoming)
+            .map(|i| graph[i].id.clone())
+            .collect::<Vec<String>>();
+        ids.sort();
+        ids
+    };
+    let hello = find("greeter.hello");
+    assert_eq!(graph[hello].executable.kind, ManifestKind::Grpc);
+    assert_eq!(parents(hello), vec!["seed.create"]);
+    assert_eq!(
+        parents(find("greeter.hello_again")),
+        vec!["greeter.hello", "seed.check"]
+    );
+    assert_eq!(parents(find("seed.check")), vec!["seed.create"]);
+}
@@ -0,0 +1,115 @@
+mod common;
+
+use std::time::{Duration, Instant};
+
+use thorust::{
+    entities::{
+        enums::TestStatus,
+        graph::{TestExecutable, TestNode},
+        manifests::RetryOn,
+    },
+    parser::parse,
+    runner::RunnerOptions,
+    services::retries::{retry_delay, should_retry},
+    traits::{Manifest, RunnerWorkflow, Storage},
+};
+
+use common::{runner, status, statuses};
+
+#[test]
+fn test_retries_from_test_or_service() {
+    let manifest = parse("tests/manifests/retries.scripts.yaml").unwrap();
+    let nodes = manifest.as_test_nodes().unwrap();
+    let retries: Vec<(u32, Option<Duration>)> = nodes
+        .iter()
+        .map(|n| (n.executable.retries, n.executable.retry_delay))
+        .collect();
+    assert_eq!(
+        retries,
+        vec![
+            (2, Some(Duration::from_millis(100))),
+            (5, Some(Duration::from_millis(100))),
+            (0, None)
+        ]
+    );
+    assert_eq!(
+        nodes[1].executable.retry_on,
+        Some(RetryOn {
+            exit_codes: vec![75],
+            output: Some("connection refused".to_string())
+        })
+    );
+}
+
+#[test]
+fn test_should_retry_and_backoff() {
+    let mut test = TestExecutable {
+        retries: 2,
+        retry_delay: Some(Duration::from_millis(100)),
+        exit_code: Some(1),
+        ..Default::default()
+    };
+    assert!(should_retry(&test, TestStatus::Failed, 1));
+    assert!(should_retry(&test, TestStatus::AssertionFailed, 2));
+    assert!(!should_retry(&test, TestStatus::Failed, 3));
+    assert!(!should_retry(&test, TestStatus::Completed, 1));
+    assert_eq!(retry_delay(&test, 1), Duration::from_millis(100));
+    assert_eq!(retry_delay(&test, 2), Duration::from_millis(200));
+    assert_eq!(retry_delay(&test, 3), Duration::from_millis(400));
+
+    test.retry_on = Some(RetryOn {
+        exit_codes: vec![75],
+        output: Some("connection (refused|reset)".to_string()),
+    });
+    assert!(!should_retry(&test, TestStatus::Failed, 1));
+    test.exit_code = Some(75);
+    assert!(should_retry(&test, TestStatus::Failed, 1));
+    test.exit_code = Some(1);
+    test.output = Some("curl: connection reset by peer".to_string());
+    assert!(should_retry(&test, TestStatus::Failed, 1));
+}
+
+#[test]
+fn test_flaky_node() {
+    let mut node = TestNode {
+        id: "foo.test1".to_string(),
+        index: 0,
+        depends_on: vec![],
+        status: vec![
+            TestStatus::NotStarted,
+            TestStatus::Running,
+            TestStatus::Retrying,
+            TestStatus::Running,
+        ],
+        executable: TestExecutable::default(),
+    };
+    assert!(!node.is_flaky());
+    node.status.push(TestStatus::Completed);
+    assert!(node.is_flaky());
+    *node.status.last_mut().unwrap() = TestStatus::Failed;
+    assert!(!node.is_flaky());
+}
+
+#[tokio::test]
+async fn test_no_retries_after_the_run_deadline() {
+    let options = RunnerOptions {
+        timeout: Some(Duration::from_millis(500)),
+        ..Default::default()
+    };
+    let (mut runner, storage) = runner("tests/manifests/retries_deadline.scripts.yaml", options);
+    let start = Instant::now();
+    runner.run_until_complete().await.unwrap();
+    // the retry delay is cut to the time left, then the last attempt is the final one
+    assert!(start.elapsed() < Duration::from_secs(5));
+    assert_eq!(
+        statuses(&runner).await,
+        vec![status("foo.fails", TestStatus::Failed)]
+    );
+    let history = storage
+        .get_node_history(runner.run_id(), 0)
+        .unwrap()
+        .into_iter()
+        .map(|h| h.status)
+        .collect::<Vec<String>>();
+    assert_eq!(history, vec!["NotStarted", "Running", "Retrying", "Failed"]);
+}
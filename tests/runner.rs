@@ -0,0 +1,51 @@
+mod common;
+
+use thorust::{
+    entities::enums::TestStatus,
+    traits::{RunnerWorkflow, Storage},
+};
+
+use common::{runner, status, statuses};
+
+#[tokio::test]
+async fn test_run_until_complete_keeps_the_history() {
+    let (mut runner, storage) = runner("tests/manifests/runner.scripts.yaml", Default::default());
+    runner.run_until_complete().await.unwrap();
+    assert_eq!(
+        statuses(&runner).await,
+        vec![
+            status("foo.fails", TestStatus::Failed),
+            status("foo.after", TestStatus::Skipped),
+            status("bar.one", TestStatus::Completed),
+            status("bar.two", TestStatus::Completed),
+        ]
+    );
+
+    let run = storage.get_last_run().unwrap().unwrap();
+    assert_eq!(run.id, runner.run_id());
+    assert!(run.finished_at.is_some());
+    assert_eq!(
+        (run.completed, run.failed, run.skipped, run.total),
+        (2, 1, 1, 4)
+    );
+    let history = storage
+        .get_node_history(run.id, 2)
+        .unwrap()
+        .into_iter()
+        .map(|h| h.status)
+        .collect::<Vec<String>>();
+    assert_eq!(history, vec!["NotStarted", "Running", "Completed"]);
+    assert_eq!(
+        storage.get_processed_node_history(run.id, 2).unwrap().len(),
+        2
+    );
+
+    // a reset starts a new run, the previous one is kept
+    runner.reset().await.unwrap();
+    assert_eq!(storage.get_runs().unwrap().len(), 2);
+    assert_eq!(
+        storage.get_node_history(runner.run_id(), 2).unwrap().len(),
+        1
+    );
+    assert_eq!(storage.get_node_history(run.id, 2).unwrap().len(), 3);
+}
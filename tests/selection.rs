@@ -0,0 +1,132 @@
+use thorust::{
+    entities::{
+        enums::TestStatus,
+        graph::{glob_matches, FilterOptions},
+    },
+    parser::parse,
+    traits::{GraphWorkflow, Manifest},
+    workflow::{Selection, Workflow},
+};
+
+fn workflow() -> Workflow {
+    Workflow::new(parse("tests/manifests/selection.scripts.yaml").unwrap()).unwrap()
+}
+
+fn ids(workflow: &Workflow) -> Vec<String> {
+    workflow
+        .graph
+        .node_weights()
+        .map(|n| n.id.clone())
+        .collect()
+}
+
+#[test]
+fn test_filter_options_match_all_fields() {
+    let manifest = parse("tests/manifests/selection.scripts.yaml").unwrap();
+    let nodes = manifest.as_test_nodes().unwrap();
+    let matching = |filter: FilterOptions| {
+        nodes
+            .iter()
+            .filter(|n| filter.check(n))
+            .map(|n| n.id.as_str())
+            .collect::<Vec<&str>>()
+    };
+    let slow_bar = FilterOptions {
+        tags: vec!["slow".to_string()],
+        service: Some("bar".to_string()),
+        ..Default::default()
+    };
+    assert_eq!(matching(slow_bar), vec!["bar.load"]);
+    // the status no longer hides the other fields
+    let not_started_foo = FilterOptions {
+        status: Some(TestStatus::NotStarted),
+        globs: vec!["foo.s*".to_string()],
+        ..Default::default()
+    };
+    assert_eq!(matching(not_started_foo), vec!["foo.setup", "foo.smoke"]);
+    assert_eq!(matching(FilterOptions::all()).len(), 7);
+
+    assert!(glob_matches("foo.*", "foo.test1"));
+    assert!(glob_matches("*.test?", "bar.test2"));
+    assert!(!glob_matches("foo.*", "bar.foo.test1"));
+    assert!(!glob_matches("foo.test", "foo.test1"));
+}
+
+#[test]
+fn test_select_by_tags_keeps_ancestors_and_teardowns() {
+    let mut workflow = workflow();
+    workflow
+        .select(Selection {
+            include: FilterOptions {
+                tags: vec!["smoke".to_string()],
+                ..Default::default()
+            },
+            exclude: None,
+        })
+        .unwrap();
+    assert_eq!(
+        ids(&workflow),
+        vec![
+            "foo.setup",
+            "foo.build",
+            "foo.smoke",
+            "foo.teardown",
+            "bar.ping"
+        ]
+    );
+    let indexes = workflow
+        .graph
+        .node_weights()
+        .map(|n| n.index)
+        .collect::<Vec<u32>>();
+    assert_eq!(indexes, vec![0, 1, 2, 3, 4]);
+    assert_eq!(
+        workflow.graph[petgraph::graph::NodeIndex::new(3)].depends_on,
+        vec!["foo.setup", "foo.build", "foo.smoke"]
+    );
+    assert!(workflow.is_cyclic().is_ok());
+}
+
+#[test]
+fn test_select_with_exclusions() {
+    let mut workflow = workflow();
+    let selection = Selection {
+        include: FilterOptions {
+            globs: vec!["foo.*".to_string()],
+            ..Default::default()
+        },
+        exclude: Some(FilterOptions {
+            globs: vec!["foo.build".to_string(), "foo.full".to_string()],
+            ..Default::default()
+        }),
+    };
+    workflow.select(selection).unwrap();
+    // foo.build is excluded, but foo.smoke depends on it
+    assert_eq!(
+        ids(&workflow),
+        vec!["foo.setup", "foo.build", "foo.smoke", "foo.teardown"]
+    );
+
+    // the selection is kept after a reset
+    let mut node = workflow.availables().unwrap()[0].clone();
+    node.status.push(TestStatus::Completed);
+    workflow.update_graph_state(node, |_, _| {});
+    workflow.reset().unwrap();
+    assert_eq!(workflow.graph.node_count(), 4);
+    assert_eq!(
+        workflow
+            .filter_graph(FilterOptions::not_started())
+            .node_count(),
+        4
+    );
+
+    let nothing = Selection {
+        include: FilterOptions {
+            tags: vec!["unknown".to_string()],
+            ..Default::default()
+        },
+        exclude: None,
+    };
+    let err = workflow.select(nothing).unwrap_err();
+    assert_eq!(err.to_string(), "No test matches the selection");
+}
@@ -0,0 +1,279 @@
+mod common;
+
+use std::{ffi::OsString, process::Command, sync::Arc};
+
+use thorust::{
+    db::{path_from, JsonLinesStorage, MemoryStorage, SqliteStorage},
+    entities::{
+        enums::TestStatus,
+        storage::{DbRun, StorageError},
+    },
+    parser::parse,
+    runner::Runner,
+    services::node_info::get_nodes_info,
+    traits::{GraphWorkflow, Manifest, RunnerWorkflow, Storage},
+    workflow::Workflow,
+};
+
+use common::{db_path, LEGACY_SCHEMA};
+
+#[test]
+fn test_sqlite_history_is_scoped_by_run() {
+    let path = db_path("storage");
+    let storage = SqliteStorage::open(&path).unwrap();
+    let nodes = parse("tests/manifests/runner.scripts.yaml")
+        .unwrap()
+        .as_test_nodes()
+        .unwrap();
+
+    let first = storage.insert_run("runner.scripts.yaml").unwrap();
+    storage
+        .insert_test_nodes(first, nodes.iter().collect())
+        .unwrap();
+    storage
+        .insert_node_history(first, "Running", 0, "")
+        .unwrap();
+    let second = storage.insert_run("runner.scripts.yaml").unwrap();
+    storage
+        .insert_test_nodes(second, nodes.iter().collect())
+        .unwrap();
+
+    // the same node ids are kept for every run
+    assert_eq!(storage.get_all_nodes(first).unwrap().len(), 4);
+    assert_eq!(storage.get_nodes(second, &[0, 3]).unwrap().len(), 2);
+    assert_eq!(storage.get_node_history(first, 0).unwrap().len(), 2);
+    assert_eq!(storage.get_node_history(second, 0).unwrap().len(), 1);
+
+    // the runs are kept when the storage is opened again
+    let storage = SqliteStorage::open(&path).unwrap();
+    let runs = storage.get_runs().unwrap();
+    assert_eq!(
+        runs.iter().map(|r| r.id).collect::<Vec<i64>>(),
+        vec![first, second]
+    );
+    assert!(runs[1].finished_at.is_none());
+    let _ = std::fs::remove_file(&path);
+}
+
+#[tokio::test]
+async fn test_database_from_before_the_runs_is_upgraded() {
+    let path = db_path("legacy");
+    rusqlite::Connection::open(&path)
+        .unwrap()
+        .execute_batch(LEGACY_SCHEMA)
+        .unwrap();
+    let storage = Arc::new(SqliteStorage::open(&path).unwrap());
+    let workflow = Workflow::new(parse("tests/manifests/runner.scripts.yaml").unwrap()).unwrap();
+    let mut runner = Runner::with_options(workflow, Default::default(), storage.clone()).unwrap();
+    runner.run_until_complete().await.unwrap();
+    assert_eq!(storage.get_all_nodes(runner.run_id()).unwrap().len(), 4);
+    assert_eq!(
+        storage.get_node_history(runner.run_id(), 2).unwrap().len(),
+        3
+    );
+    // the old history is kept apart
+    let legacy: i64 = storage
+        .conn()
+        .query_row("SELECT COUNT(*) FROM node_history_legacy", [], |row| {
+            row.get(0)
+        })
+        .unwrap();
+    assert_eq!(legacy, 1);
+    let _ = std::fs::remove_file(&path);
+}
+
+#[tokio::test]
+async fn test_nodes_info_by_run() {
+    let storage = Arc::new(MemoryStorage::new());
+    let workflow = Workflow::new(parse("tests/manifests/runner.scripts.yaml").unwrap()).unwrap();
+    let mut runner = Runner::with_options(workflow, Default::default(), storage.clone()).unwrap();
+    runner.run_until_complete().await.unwrap();
+    let first = runner.run_id();
+    runner.reset().await.unwrap();
+
+    // the nodes of each run, as the API gives them with `?run=`
+    let data = |run| {
+        get_nodes_info(&*storage, run)
+            .unwrap()
+            .into_iter()
+            .map(|node| node.data)
+            .collect::<Vec<String>>()
+    };
+    assert_eq!(data(first), vec!["", "", "one\n", "two\n"]);
+    assert_eq!(data(runner.run_id()), vec!["", "", "", ""]);
+}
+
+#[test]
+fn test_runs_command_lists_the_runs() {
+    let path = db_path("cli");
+    let cli = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_cli"))
+            .arg("--db")
+            .arg(&path)
+            .args(args)
+            .output()
+            .unwrap()
+    };
+    for _ in 0..2 {
+        let output = cli(&["run", "--file", "tests/manifests/runner.scripts.yaml"]);
+        assert!(output.status.success(), "{:?}", output);
+    }
+    let runs: Vec<DbRun> = serde_json::from_slice(&cli(&["runs"]).stdout).unwrap();
+    assert_eq!(
+        runs.iter()
+            .map(|r| (r.completed, r.failed, r.skipped, r.total))
+            .collect::<Vec<_>>(),
+        vec![(2, 1, 1, 4), (2, 1, 1, 4)]
+    );
+    assert!(runs.iter().all(|r| r.finished_at.is_some()));
+    let _ = std::fs::remove_file(&path);
+}
+
+#[tokio::test]
+async fn test_runs_driven_by_waves_are_finished() {
+    let storage = Arc::new(MemoryStorage::new());
+    let workflow = Workflow::new(parse("tests/manifests/runner.scripts.yaml").unwrap()).unwrap();
+    let mut runner = Runner::with_options(workflow, Default::default(), storage.clone()).unwrap();
+    // as the UI does, through `/api/runner/batch`
+    loop {
+        let availables = runner.workflow.read().await.availables().unwrap();
+        if availables.is_empty() {
+            break;
+        }
+        runner.batch_execute(availables).await.unwrap();
+    }
+    // the next run is reset halfway, after its first wave
+    runner.reset().await.unwrap();
+    let availables = runner.workflow.read().await.availables().unwrap();
+    runner.batch_execute(availables).await.unwrap();
+    runner.reset().await.unwrap();
+
+    let runs = storage.get_runs().unwrap();
+    assert_eq!(
+        runs.iter()
+            .map(|r| (r.completed, r.failed, r.skipped, r.total))
+            .collect::<Vec<_>>(),
+        vec![(2, 1, 1, 4), (1, 1, 1, 4), (0, 0, 0, 0)]
+    );
+    assert!(runs[..2].iter().all(|r| r.finished_at.is_some()));
+    assert!(runs[2].finished_at.is_none());
+}
+
+#[test]
+fn test_path_from_env() {
+    assert_eq!(
+        path_from(Some(OsString::from("/tmp/thorust.db"))).to_str(),
+        Some("/tmp/thorust.db")
+    );
+    assert_eq!(path_from(None).to_str(), Some("./db"));
+}
+
+#[test]
+fn test_concurrent_writers_wait_for_the_lock() {
+    let path = db_path("writers");
+    let storage = SqliteStorage::open(&path).unwrap();
+    let nodes = parse("tests/manifests/runner.scripts.yaml")
+        .unwrap()
+        .as_test_nodes()
+        .unwrap();
+    let run = storage.insert_run("writers").unwrap();
+    storage
+        .insert_test_nodes(run, nodes.iter().collect())
+        .unwrap();
+    // every writer has its own connection, as different processes would
+    let writers = (0..4)
+        .map(|_| {
+            let storage = SqliteStorage::open(&path).unwrap();
+            std::thread::spawn(move || {
+                for i in 0..40 {
+                    storage
+                        .insert_node_history(run, "Running", i % 4, "")
+                        .unwrap();
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+    for writer in writers {
+        writer.join().unwrap();
+    }
+    assert_eq!(storage.get_node_history(run, 0).unwrap().len(), 41);
+    let _ = std::fs::remove_file(&path);
+}
+
+#[tokio::test]
+async fn test_storage_errors_fail_the_run() {
+    let path = db_path("errors");
+    let storage = Arc::new(SqliteStorage::open(&path).unwrap());
+    let workflow = Workflow::new(parse("tests/manifests/runner.scripts.yaml").unwrap()).unwrap();
+    let mut runner = Runner::with_options(workflow, Default::default(), storage).unwrap();
+    rusqlite::Connection::open(&path)
+        .unwrap()
+        .execute("DROP TABLE node_history", ())
+        .unwrap();
+    let err = runner.run_until_complete().await.unwrap_err();
+    match err.downcast_ref::<StorageError>() {
+        Some(StorageError::Database(message)) => {
+            assert!(message.contains("no such table: node_history"))
+        }
+        _ => panic!("unexpected error: {}", err),
+    }
+    // the run is cancelled, no test is left behind or running
+    let workflow = runner.workflow.read().await;
+    assert!(workflow.graph.node_weights().all(|n| !matches!(
+        n.last_status(),
+        TestStatus::NotStarted | TestStatus::Running | TestStatus::Retrying
+    )));
+    assert!(workflow
+        .graph
+        .node_weights()
+        .any(|n| n.last_status() == TestStatus::Failed
+            && n.executable
+                .reason
+                .as_deref()
+                .unwrap_or("")
+                .contains("no such table")));
+    let _ = std::fs::remove_file(&path);
+}
+
+#[tokio::test]
+async fn test_json_lines_storage_keeps_the_runs() {
+    let dir = db_path("jsonl");
+    let _ = std::fs::remove_dir_all(&dir);
+    for _ in 0..2 {
+        let storage = Arc::new(JsonLinesStorage::open(&dir).unwrap());
+        let workflow =
+            Workflow::new(parse("tests/manifests/runner.scripts.yaml").unwrap()).unwrap();
+        let mut runner = Runner::with_options(workflow, Default::default(), storage).unwrap();
+        runner.run_until_complete().await.unwrap();
+    }
+
+    let storage = JsonLinesStorage::open(&dir).unwrap();
+    let runs = storage.get_runs().unwrap();
+    assert_eq!(runs.iter().map(|r| r.id).collect::<Vec<i64>>(), vec![1, 2]);
+    assert!(runs[1].finished_at.is_some());
+    assert_eq!(
+        (runs[1].completed, runs[1].failed, runs[1].total),
+        (2, 1, 4)
+    );
+    assert_eq!(storage.get_all_nodes(2).unwrap().len(), 4);
+    let history = storage
+        .get_node_history(2, 2)
+        .unwrap()
+        .into_iter()
+        .map(|h| h.status)
+        .collect::<Vec<String>>();
+    assert_eq!(history, vec!["NotStarted", "Running", "Completed"]);
+    assert_eq!(storage.get_processed_node_history(2, 2).unwrap().len(), 2);
+    // the ids keep growing across the runs
+    let ids = storage
+        .get_node_history(1, 0)
+        .unwrap()
+        .into_iter()
+        .chain(storage.get_node_history(2, 0).unwrap())
+        .map(|h| h.id)
+        .collect::<Vec<i32>>();
+    assert!(ids.windows(2).all(|pair| pair[0] < pair[1]));
+    let lines = std::fs::read_to_string(dir.join("runs.jsonl")).unwrap();
+    assert_eq!(lines.lines().count(), 4);
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,49 @@
+mod common;
+
+use thorust::{entities::enums::TestStatus, traits::Storage};
+
+use common::{runner, status, statuses};
+
+#[tokio::test]
+async fn test_run_target_runs_only_its_ancestors() {
+    let (mut runner, _) = runner("tests/manifests/runner.scripts.yaml", Default::default());
+    let node = runner.run_target("bar.two", false).await.unwrap();
+    assert_eq!(node.last_status(), TestStatus::Completed);
+    assert_eq!(
+        statuses(&runner).await,
+        vec![
+            status("foo.fails", TestStatus::NotStarted),
+            status("foo.after", TestStatus::NotStarted),
+            status("bar.one", TestStatus::Completed),
+            status("bar.two", TestStatus::Completed),
+        ]
+    );
+    assert!(runner.run_target("bar.three", false).await.is_err());
+}
+
+#[tokio::test]
+async fn test_run_target_reuses_the_completed_ancestors() {
+    let (mut runner, storage) = runner("tests/manifests/runner.scripts.yaml", Default::default());
+    runner.run_target("bar.two", false).await.unwrap();
+    let node = runner.run_target("bar.two", true).await.unwrap();
+    assert_eq!(node.last_status(), TestStatus::Completed);
+    let runs = |id| {
+        storage
+            .get_node_history(runner.run_id(), id)
+            .unwrap()
+            .into_iter()
+            .filter(|h| h.status == "Running")
+            .count()
+    };
+    // bar.one has only run once, bar.two twice
+    assert_eq!((runs(2), runs(3)), (1, 2));
+}
+
+#[tokio::test]
+async fn test_run_target_always_starts_the_daemons_again() {
+    let (mut runner, _) = runner("tests/manifests/daemons.scripts.yaml", Default::default());
+    runner.run_target("api.health", false).await.unwrap();
+    // the server was killed at the end of the first run, api.health checks its pid
+    let node = runner.run_target("api.health", true).await.unwrap();
+    assert_eq!(node.last_status(), TestStatus::Completed);
+}
@@ -0,0 +1,90 @@
+use std::collections::HashMap;
+
+use petgraph::stable_graph::NodeIndex;
+use thorust::{
+    entities::{
+        enums::TestStatus,
+        graph::{TestExecutable, TestNode},
+    },
+    parser::parse,
+    services::templates::{references, render, TemplateRef},
+    traits::GraphWorkflow,
+    workflow::Workflow,
+};
+
+#[test]
+fn test_references_and_render() {
+    let text = "echo {{ foo.test1.output }} {{foo.test1.exit_code}} {{ bar.output }}";
+    assert_eq!(
+        references(text),
+        vec![
+            TemplateRef {
+                test_id: "foo.test1".to_string(),
+                field: "output".to_string()
+            },
+            TemplateRef {
+                test_id: "foo.test1".to_string(),
+                field: "exit_code".to_string()
+            },
+            TemplateRef {
+                test_id: "bar".to_string(),
+                field: "output".to_string()
+            },
+        ]
+    );
+
+    let node = TestNode {
+        id: "foo.test1".to_string(),
+        index: 0,
+        status: vec![TestStatus::Completed],
+        depends_on: vec![],
+        executable: TestExecutable {
+            output: Some("42\n".to_string()),
+            exit_code: Some(0),
+            ..Default::default()
+        },
+    };
+    let context = HashMap::from([("foo.test1".to_string(), &node)]);
+    assert_eq!(
+        render(
+            "echo {{ foo.test1.output }} {{foo.test1.exit_code}}",
+            &context
+        )
+        .unwrap(),
+        "echo 42 0"
+    );
+    assert!(render("echo {{ foo.test2.output }}", &context).is_err());
+}
+
+#[test]
+fn test_render_templates_with_ancestors_results() {
+    let manifest = parse("tests/manifests/templates.scripts.yaml").unwrap();
+    let mut workflow = Workflow::new(manifest).unwrap();
+    for (index, output) in [(0, "42\n"), (1, "user 42\n")] {
+        let mut node = workflow.graph[NodeIndex::new(index)].clone();
+        node.executable.output = Some(output.to_string());
+        node.executable.exit_code = Some(0);
+        node.status.push(TestStatus::Completed);
+        workflow.update_graph_state(node, |_, _| {});
+    }
+
+    let mut node = workflow.graph[NodeIndex::new(2)].clone();
+    assert_eq!(node.id, "bar.delete");
+    let context: HashMap<String, &TestNode> = workflow
+        .ancestors(NodeIndex::new(2))
+        .into_iter()
+        .map(|n| (n.id.clone(), n))
+        .collect();
+    node.executable.render_templates(&context).unwrap();
+    assert_eq!(node.executable.command, r#"echo "delete 42 after 0""#);
+}
+
+#[test]
+fn test_template_referring_a_non_ancestor_fails_on_normalize() {
+    let err = parse("tests/manifests/invalid_templates.scripts.yaml").unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Found 1 problem(s) in the manifests:\n  tests/manifests/invalid_templates.scripts.yaml:9:9: \
+        The test id 'foo.get' refers to 'foo.create.output', but 'foo.create' is not one of its dependencies"
+    );
+}
@@ -0,0 +1,81 @@
+mod common;
+
+use std::time::{Duration, Instant};
+
+use thorust::{
+    entities::{enums::TestStatus, graph::TestExecutable},
+    parser::parse,
+    runner::RunnerOptions,
+    services::assertions::final_status,
+    traits::{Manifest, RunnerWorkflow},
+};
+
+use common::{is_alive, reason, runner, status, statuses};
+
+#[test]
+fn test_timeout_from_test_or_service() {
+    let manifest = parse("tests/manifests/timeouts.scripts.yaml").unwrap();
+    let timeouts: Vec<Option<Duration>> = manifest
+        .as_test_nodes()
+        .unwrap()
+        .iter()
+        .map(|n| n.executable.timeout)
+        .collect();
+    assert_eq!(
+        timeouts,
+        vec![
+            Some(Duration::from_secs(60)),
+            Some(Duration::from_millis(1500)),
+            None
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_timed_out_script_kills_its_process_tree() {
+    let pid_file = std::env::temp_dir().join(format!("thorust-timeout-{}", std::process::id()));
+    let mut test = TestExecutable {
+        id: "foo.sleep".to_string(),
+        name: "sleep".to_string(),
+        command: format!("sleep 30 & echo $! > {}; wait", pid_file.display()),
+        timeout: Some(Duration::from_millis(300)),
+        ..Default::default()
+    };
+    let start = Instant::now();
+    let call = test.call().await;
+    assert!(start.elapsed() < Duration::from_secs(5));
+    assert!(call.is_err());
+    assert_eq!(test.reason, Some("timed out after 300ms".to_string()));
+    assert_eq!(final_status(&mut test, &call), TestStatus::Failed);
+
+    // the background sleep belongs to the same process group, so it must be killed too,
+    // it may be kept as a zombie until its new parent reaps it.
+    let pid = std::fs::read_to_string(&pid_file).unwrap();
+    let _ = std::fs::remove_file(&pid_file);
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(!is_alive(&pid), "the process {} is still alive", pid.trim());
+}
+
+#[tokio::test]
+async fn test_nothing_starts_after_the_run_deadline() {
+    let options = RunnerOptions {
+        timeout: Some(Duration::from_millis(500)),
+        jobs: Some(1),
+        ..Default::default()
+    };
+    let (mut runner, _) = runner("tests/manifests/deadline.scripts.yaml", options);
+    let start = Instant::now();
+    runner.run_until_complete().await.unwrap();
+    assert!(start.elapsed() < Duration::from_secs(5));
+    assert_eq!(
+        statuses(&runner).await,
+        vec![
+            status("foo.slow", TestStatus::Failed),
+            status("foo.quick", TestStatus::Cancelled),
+        ]
+    );
+    assert_eq!(
+        reason(&runner, "foo.quick").await.as_deref(),
+        Some("not started, run timed out")
+    );
+}
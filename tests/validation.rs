@@ -0,0 +1,86 @@
+use thorust::{
+    entities::validation::{ValidationErrors, ValidationProblem},
+    parser::parse,
+};
+
+fn problem(file: &str, line: usize, column: usize, message: &str) -> String {
+    format!(
+        "tests/manifests/invalid/{}:{}:{}: {}",
+        file, line, column, message
+    )
+}
+
+#[test]
+fn test_validation_collects_all_problems_with_locations() {
+    let err = parse("tests/manifests/invalid").unwrap_err();
+    let errors = err.downcast_ref::<ValidationErrors>().unwrap();
+    let problems: Vec<String> = errors
+        .problems
+        .iter()
+        .map(ValidationProblem::to_string)
+        .collect();
+    assert_eq!(
+        problems,
+        vec![
+            problem(
+                "problems.grpc.yaml",
+                3,
+                5,
+                "The service name 'foo' is duplicated, first declared at tests/manifests/invalid/problems.scripts.yaml:3:5"
+            ),
+            problem(
+                "problems.scripts.yaml",
+                9,
+                9,
+                "The test id 'foo.first' is duplicated, first declared at tests/manifests/invalid/problems.scripts.yaml:5:9"
+            ),
+            problem(
+                "problems.scripts.yaml",
+                13,
+                9,
+                "The test id 'foo.empty' depends on 'foo.frist', which does not exist"
+            ),
+            problem(
+                "problems.scripts.yaml",
+                13,
+                9,
+                "The test id 'foo.empty' depends on 'foo.missing', which does not exist"
+            ),
+            problem(
+                "problems.scripts.yaml",
+                13,
+                9,
+                "The test id 'foo.empty' has an empty command"
+            ),
+            problem(
+                "problems.scripts.yaml",
+                21,
+                9,
+                "The test id 'foo.itself' depends on itself"
+            ),
+            problem(
+                "problems.scripts.yaml",
+                27,
+                9,
+                "The test id 'foo.twice' depends on 'foo.first' more than once, a dependency has a single condition"
+            ),
+            problem(
+                "problems.grpc.yaml",
+                6,
+                9,
+                "The test id 'foo.hello' has an empty method"
+            ),
+        ]
+    );
+}
+
+#[test]
+fn test_deserialization_error_has_location() {
+    let err = parse("tests/manifests/broken.scripts.yaml").unwrap_err();
+    let errors = err.downcast_ref::<ValidationErrors>().unwrap();
+    assert_eq!(errors.problems.len(), 1);
+    let problem = &errors.problems[0];
+    assert_eq!(problem.location.file, "tests/manifests/broken.scripts.yaml");
+    assert_eq!(problem.location.line, 5);
+    assert!(problem.message.contains("missing field `command`"));
+}
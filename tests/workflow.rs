@@ -6,19 +6,19 @@ use petgraph::{
 
 use thorust::{
     entities::{
-        enums::TestStatus,
-        graph::{FilterOptions, TestNode, TestExecutable},
+        enums::{DependencyCondition, TestStatus},
+        graph::{FilterOptions, TestExecutable, TestNode},
     },
     parser::parse,
     traits::GraphWorkflow,
 };
 
-use thorust::workflow::Workflow;
+use thorust::workflow::{Workflow, MAX_CYCLES};
 
 #[test]
 fn test_dot_render_with_update_graph_status_on_cascade() {
     let manifest = parse("manifests_example/example.scripts.yaml").unwrap();
-    let mut workflow =  Workflow::new(manifest).unwrap();
+    let mut workflow = Workflow::new(manifest).unwrap();
     let availables = workflow.availables().unwrap();
     let mut node = availables
         .iter()
@@ -131,7 +131,7 @@ fn test_dot_render_with_update_graph_status_on_cascade() {
 #[test]
 fn test_dot_render_with_update_graph_status_on_cascade_should_only_affect_directional_nodes() {
     let manifest = parse("manifests_example/example.scripts.yaml").unwrap();
-    let mut workflow =  Workflow::new(manifest).unwrap();
+    let mut workflow = Workflow::new(manifest).unwrap();
 
     let node_idx = NodeIndex::new(4);
     let mut node = workflow.graph[node_idx].clone();
@@ -171,7 +171,7 @@ fn test_dot_render_with_update_graph_status_on_cascade_should_only_affect_direct
 
 #[test]
 fn test_filter_nodes_with_filter_options() {
-    let mut graph = DiGraph::<TestNode, usize>::new();
+    let mut graph = DiGraph::<TestNode, DependencyCondition>::new();
     let a = graph.add_node(TestNode {
         id: "a".to_string(),
         index: 0,
@@ -193,8 +193,8 @@ fn test_filter_nodes_with_filter_options() {
         depends_on: vec![],
         executable: TestExecutable::default(),
     });
-    graph.add_edge(a, b, 0);
-    graph.add_edge(b, c, 0);
+    graph.add_edge(a, b, DependencyCondition::Success);
+    graph.add_edge(b, c, DependencyCondition::Success);
 
     let workflow = Workflow::from_graph(graph);
     let not_started = workflow.filter_graph(FilterOptions::not_started());
@@ -204,3 +204,197 @@ fn test_filter_nodes_with_filter_options() {
     assert_eq!(completed.node_count(), 1);
     assert_eq!(all.node_count(), 3);
 }
+
+#[test]
+fn test_update_graph_status_on_assertion_failed_cascades_skipped() {
+    let manifest = parse("manifests_example/example.scripts.yaml").unwrap();
+    let mut workflow = Workflow::new(manifest).unwrap();
+
+    let mut node = workflow.graph[NodeIndex::new(4)].clone();
+    assert_eq!(node.id, "foo.test5");
+    node.status.push(TestStatus::AssertionFailed);
+    workflow.update_graph_state(node, |_, _| {});
+
+    let skipped = workflow.filter_graph(FilterOptions::skipped());
+    let mut skipped_ids = skipped
+        .node_weights()
+        .map(|n| n.id.clone())
+        .collect::<Vec<String>>();
+    skipped_ids.sort();
+    assert_eq!(skipped_ids, vec!["bar.test1", "foo.test6", "foo.test7"]);
+}
+
+#[test]
+fn test_is_cyclic_reports_every_cycle_path() {
+    let manifest = parse("tests/manifests/cyclic.scripts.yaml").unwrap();
+    let workflow = Workflow::new(manifest).unwrap();
+    let err = workflow.is_cyclic().unwrap_err();
+    let file = "tests/manifests/cyclic.scripts.yaml";
+    assert_eq!(
+        err.to_string(),
+        format!(
+            "The test workflow has cyclic dependencies:
+  foo.first -> foo.second -> foo.first
+    'foo.first' depends on 'foo.second' in {file}:5:9
+    'foo.second' depends on 'foo.first' in {file}:11:9
+  bar.test3 -> bar.test4 -> bar.test5 -> bar.test3
+    'bar.test3' depends on 'bar.test4' in {file}:19:9
+    'bar.test4' depends on 'bar.test5' in {file}:25:9
+    'bar.test5' depends on 'bar.test3' in {file}:32:9
+  baz.hub -> baz.left -> baz.hub
+    'baz.hub' depends on 'baz.left' in {file}:40:9
+    'baz.left' depends on 'baz.hub' in {file}:47:9
+  baz.hub -> baz.right -> baz.hub
+    'baz.hub' depends on 'baz.right' in {file}:40:9
+    'baz.right' depends on 'baz.hub' in {file}:53:9"
+        )
+    );
+}
+
+#[test]
+fn test_cycles_are_capped() {
+    let manifest = parse("tests/manifests/dense.scripts.yaml").unwrap();
+    let workflow = Workflow::new(manifest).unwrap();
+    assert_eq!(workflow.cycles().len(), MAX_CYCLES);
+    let err = workflow.is_cyclic().unwrap_err().to_string();
+    assert!(err.ends_with(&format!("(only the first {} cycles are shown)", MAX_CYCLES)));
+}
+
+#[test]
+fn test_availables_waits_for_running_dependencies() {
+    let manifest = parse("manifests_example/example.scripts.yaml").unwrap();
+    let mut workflow = Workflow::new(manifest).unwrap();
+    let ids = |workflow: &Workflow| {
+        workflow
+            .availables()
+            .unwrap()
+            .iter()
+            .map(|n| n.id.clone())
+            .collect::<Vec<String>>()
+    };
+    let push = |workflow: &mut Workflow, index: usize, status: TestStatus| {
+        let mut node = workflow.graph[NodeIndex::new(index)].clone();
+        node.status.push(status);
+        workflow.update_graph_state(node, |_, _| {});
+    };
+    // foo.test4 depends on foo.test2 and foo.test3, it must wait until both are completed
+    push(&mut workflow, 1, TestStatus::Completed);
+    push(&mut workflow, 2, TestStatus::Running);
+    assert_eq!(ids(&workflow), vec!["foo.test1", "bar.test2"]);
+
+    push(&mut workflow, 2, TestStatus::Completed);
+    assert_eq!(ids(&workflow), vec!["foo.test1", "foo.test4", "bar.test2"]);
+}
+
+fn statuses(workflow: &Workflow) -> Vec<(String, TestStatus)> {
+    workflow
+        .graph
+        .node_weights()
+        .map(|n| (n.id.clone(), n.last_status()))
+        .collect()
+}
+
+fn ids(nodes: Vec<TestNode>) -> Vec<String> {
+    nodes.into_iter().map(|n| n.id).collect()
+}
+
+#[test]
+fn test_conditional_dependencies_on_failure() {
+    let manifest = parse("tests/manifests/conditions.scripts.yaml").unwrap();
+    let mut workflow = Workflow::new(manifest).unwrap();
+    assert_eq!(
+        workflow.graph.edge_weights().collect::<Vec<_>>(),
+        vec![
+            &DependencyCondition::Success,
+            &DependencyCondition::Failure,
+            &DependencyCondition::Always,
+            &DependencyCondition::Always
+        ]
+    );
+
+    let mut deploy = workflow.graph[NodeIndex::new(0)].clone();
+    deploy.status.push(TestStatus::Failed);
+    workflow.update_graph_state(deploy, |_, _| {});
+    // smoke is skipped, but cleanup always runs, even after a skipped test
+    assert_eq!(
+        statuses(&workflow),
+        vec![
+            ("foo.deploy".to_string(), TestStatus::Failed),
+            ("foo.smoke".to_string(), TestStatus::Skipped),
+            ("foo.logs".to_string(), TestStatus::NotStarted),
+            ("foo.cleanup".to_string(), TestStatus::NotStarted),
+        ]
+    );
+    assert_eq!(
+        ids(workflow.availables().unwrap()),
+        vec!["foo.logs", "foo.cleanup"]
+    );
+}
+
+#[test]
+fn test_always_is_not_satisfied_by_a_cancelled_test() {
+    let manifest = parse("tests/manifests/conditions.scripts.yaml").unwrap();
+    let mut workflow = Workflow::new(manifest).unwrap();
+
+    let mut deploy = workflow.graph[NodeIndex::new(0)].clone();
+    deploy.status.push(TestStatus::Completed);
+    workflow.update_graph_state(deploy, |_, _| {});
+    let mut smoke = workflow.graph[NodeIndex::new(1)].clone();
+    smoke.status.push(TestStatus::Cancelled);
+    workflow.update_graph_state(smoke, |_, _| {});
+    // cleanup is left to the cancelled run
+    assert!(workflow.availables().unwrap().is_empty());
+    assert_eq!(
+        workflow.graph[NodeIndex::new(3)].last_status(),
+        TestStatus::NotStarted
+    );
+}
+
+#[test]
+fn test_conditional_dependencies_on_success() {
+    let manifest = parse("tests/manifests/conditions.scripts.yaml").unwrap();
+    let mut workflow = Workflow::new(manifest).unwrap();
+
+    let mut deploy = workflow.graph[NodeIndex::new(0)].clone();
+    deploy.status.push(TestStatus::Completed);
+    workflow.update_graph_state(deploy, |_, _| {});
+    assert_eq!(
+        workflow.graph[NodeIndex::new(2)].last_status(),
+        TestStatus::Skipped
+    );
+    // cleanup still waits for smoke
+    assert_eq!(ids(workflow.availables().unwrap()), vec!["foo.smoke"]);
+
+    let mut smoke = workflow.graph[NodeIndex::new(1)].clone();
+    smoke.status.push(TestStatus::AssertionFailed);
+    workflow.update_graph_state(smoke, |_, _| {});
+    assert_eq!(ids(workflow.availables().unwrap()), vec!["foo.cleanup"]);
+}
+
+#[test]
+fn test_find_and_reset_nodes() {
+    let manifest = parse("manifests_example/example.scripts.yaml").unwrap();
+    let mut workflow = Workflow::new(manifest).unwrap();
+    assert_eq!(workflow.find_node("bar.test1").unwrap().index, 7);
+    assert_eq!(workflow.find_node("7").unwrap().id, "bar.test1");
+    assert!(workflow.find_node("bar.unknown").is_none());
+
+    let mut node = workflow.graph[NodeIndex::new(0)].clone();
+    node.executable.command = "rendered".to_string();
+    node.status.push(TestStatus::Failed);
+    workflow.update_graph_state(node, |_, _| {});
+    assert_eq!(
+        workflow.graph[NodeIndex::new(4)].last_status(),
+        TestStatus::Skipped
+    );
+
+    // only the given nodes are reset, with their initial command
+    workflow.reset_nodes(&[0].into_iter().collect()).unwrap();
+    let node = &workflow.graph[NodeIndex::new(0)];
+    assert_eq!(node.status, vec![TestStatus::NotStarted]);
+    assert_eq!(node.executable.command, "sleep 3; echo \"test1 foo\"");
+    assert_eq!(
+        workflow.graph[NodeIndex::new(4)].last_status(),
+        TestStatus::Skipped
+    );
+}